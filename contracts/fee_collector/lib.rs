@@ -0,0 +1,130 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+/// Collects the PSP22 fees of meta transactions on behalf of a forwarder.
+///
+/// Signers approve this contract rather than the forwarder to spend their fee tokens. A forwarder
+/// calls arbitrary contracts on behalf of its signers, so any allowance it held could be spent by a
+/// forwarded `PSP22::transfer_from`. This contract never forwards calls, and the forwarder refuses
+/// to forward calls to it, so only fees the signers agreed to can be collected.
+#[ink::contract]
+mod fee_collector {
+    use fee_collector_trait::FeeCollectorError;
+    use ink::{
+        env::call::{
+            build_call,
+            ExecutionInput,
+            Selector,
+        },
+        prelude::vec::Vec,
+    };
+
+    #[ink(storage)]
+    pub struct FeeCollector {
+        /// The only forwarder allowed to collect fees.
+        forwarder: AccountId,
+    }
+
+    /// `amount` of `token` was collected from `from` and paid to `to`
+    #[ink(event)]
+    pub struct Collected {
+        #[ink(topic)]
+        token: AccountId,
+        #[ink(topic)]
+        from: AccountId,
+        to: AccountId,
+        amount: Balance,
+    }
+
+    impl FeeCollector {
+        /// Creates a fee collector for `forwarder`.
+        #[ink(constructor)]
+        pub fn new(forwarder: AccountId) -> Self {
+            Self { forwarder }
+        }
+
+        #[ink(message)]
+        pub fn get_forwarder(&self) -> AccountId {
+            self.forwarder
+        }
+    }
+
+    impl fee_collector_trait::FeeCollector for FeeCollector {
+        /// Forwarder only.
+        #[ink(message)]
+        fn collect(
+            &mut self,
+            token: AccountId,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+        ) -> Result<(), FeeCollectorError> {
+            if self.env().caller() != self.forwarder {
+                return Err(FeeCollectorError::CallerNotForwarder)
+            }
+
+            let result = build_call::<<Self as ::ink::env::ContractEnv>::Env>()
+                .call(token)
+                .gas_limit(0)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!(
+                        "PSP22::transfer_from"
+                    )))
+                    .push_arg(from)
+                    .push_arg(to)
+                    .push_arg(amount)
+                    .push_arg(Vec::<u8>::new()),
+                )
+                .returns::<Result<(), PSP22Error>>()
+                .try_invoke();
+
+            match result {
+                Ok(Ok(Ok(()))) => {
+                    self.env().emit_event(Collected {
+                        token,
+                        from,
+                        to,
+                        amount,
+                    });
+                    Ok(())
+                }
+                _ => Err(FeeCollectorError::TransferFailed),
+            }
+        }
+    }
+
+    /// Decodes any PSP22 error, only whether the transfer succeeded matters.
+    struct PSP22Error;
+
+    impl scale::Decode for PSP22Error {
+        fn decode<I: scale::Input>(input: &mut I) -> Result<Self, scale::Error> {
+            let len = input.remaining_len()?.unwrap_or(0);
+            input.read(&mut ink::prelude::vec![0; len])?;
+            Ok(Self)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use fee_collector_trait::FeeCollector as _;
+
+        #[ink::test]
+        fn new_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let fee_collector = FeeCollector::new(accounts.bob);
+            assert_eq!(fee_collector.get_forwarder(), accounts.bob);
+        }
+
+        #[ink::test]
+        fn collect_by_non_forwarder_fails() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut fee_collector = FeeCollector::new(accounts.bob);
+
+            ink::env::test::set_caller::<Environment>(accounts.eve);
+            assert_eq!(
+                fee_collector.collect(accounts.charlie, accounts.django, accounts.eve, 100),
+                Err(FeeCollectorError::CallerNotForwarder)
+            );
+        }
+    }
+}
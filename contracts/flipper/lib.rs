@@ -1,9 +1,13 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 #![feature(min_specialization)]
 
+#[meta_tx_context::meta_tx_contract]
 #[ink::contract]
 mod flipper {
-    use ink::prelude::vec::Vec;
+    use ink::codegen::{
+        EmitEvent,
+        Env,
+    };
     use meta_tx_context::*;
     use openbrush::{
         contracts::access_control::*,
@@ -26,7 +30,40 @@ mod flipper {
     }
 
     impl AccessControl for Flipper {}
-    impl MetaTxContext for Flipper {}
+
+    /// The set of trusted forwarders is going to change once `eta` is reached
+    #[ink(event)]
+    pub struct TrustedForwarderProposed {
+        forwarder: TrustedForwarder,
+        eta: Timestamp,
+    }
+
+    /// `forwarder` is now trusted, or no longer trusted
+    #[ink(event)]
+    pub struct TrustedForwarderChanged {
+        forwarder: TrustedForwarder,
+        trusted: bool,
+    }
+
+    impl MetaTxContext for Flipper {
+        fn _emit_trusted_forwarder_proposed_event(
+            &self,
+            forwarder: TrustedForwarder,
+            eta: Timestamp,
+        ) {
+            self.env()
+                .emit_event(TrustedForwarderProposed { forwarder, eta });
+        }
+
+        fn _emit_trusted_forwarder_changed_event(
+            &self,
+            forwarder: TrustedForwarder,
+            trusted: bool,
+        ) {
+            self.env()
+                .emit_event(TrustedForwarderChanged { forwarder, trusted });
+        }
+    }
 
     /// Errors that can occur upon calling this contract.
     #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
@@ -47,9 +84,9 @@ mod flipper {
         pub fn new(trusted_forwarder: AccountId, init_value: bool) -> Self {
             let mut _instance = Self::default();
             _instance._init_with_admin(_instance.env().caller());
-            _instance
-                .set_trusted_forwarder(trusted_forwarder)
-                .expect("Should have MANAGER role");
+            _instance._setup_role(MANAGER, _instance.env().caller());
+            _instance._setup_role(GUARDIAN, _instance.env().caller());
+            _instance._add_trusted_forwarder(TrustedForwarder::Account(trusted_forwarder));
             _instance.value = init_value;
             _instance
         }
@@ -64,8 +101,17 @@ mod flipper {
 
         // Same flip functionality with meta context.
         #[ink(message)]
-        pub fn flip_meta_context(&mut self, data: Vec<u8>) -> Result<(), Error> {
-            let _caller = self._caller(data)?;
+        #[meta_tx(_caller)]
+        pub fn flip_meta_context(&mut self) -> Result<(), Error> {
+            ink::env::debug_println!("Flip called by {:?}", _caller);
+            self.value = !self.value;
+            Ok(())
+        }
+
+        // Same flip functionality, for forwarders appending the signer to the call input.
+        #[ink(message)]
+        pub fn flip_msg_sender(&mut self) -> Result<(), Error> {
+            let _caller = self._msg_sender()?;
             ink::env::debug_println!("Flip called by {:?}", _caller);
             self.value = !self.value;
             Ok(())
@@ -1,4 +1,5 @@
 #![cfg_attr(not(feature = "std"), no_std)]
+#![feature(min_specialization)]
 
 #[ink::contract]
 mod forwarder {
@@ -7,18 +8,151 @@ mod forwarder {
             call::{
                 build_call,
                 ExecutionInput,
+                Selector,
             },
             CallFlags,
         },
         prelude::vec::Vec,
         storage::Mapping,
     };
+    use openbrush::{
+        contracts::access_control::*,
+        modifiers,
+        traits::Storage,
+    };
     use scale::{
+        Decode,
         Encode,
         Output,
     };
 
     pub type Nonce = u128;
+    /// Identifies one of a signer's independent nonce sequences.
+    pub type NonceKey = u64;
+    /// Signer-wide counter; bumping it invalidates every signature made for an earlier epoch.
+    pub type Epoch = u32;
+    /// Selector of a message of a callee.
+    pub type MessageSelector = [u8; 4];
+
+    /// Role of the relayers allowed to submit transactions while the forwarder is permissioned.
+    pub const RELAYER: RoleType = ink::selector_id!("RELAYER");
+    /// Role allowed to pause the forwarder in an emergency.
+    pub const GUARDIAN: RoleType = ink::selector_id!("GUARDIAN");
+
+    /// Name of the signing domain, part of every domain separator.
+    pub const DOMAIN_NAME: &str = "Forwarder";
+    /// Version of the signing domain.
+    pub const DOMAIN_VERSION: &str = "1";
+
+    /// Selector of the message `$message` of the ink! trait `$trait`, namespaced by the trait as its
+    /// definition computes it. Fails to compile if the trait has no such message.
+    macro_rules! trait_selector {
+        ($trait:path, $message:literal) => {
+            <<ink::reflect::TraitDefinitionRegistry<Environment> as $trait>::__ink_TraitInfo
+                as ink::reflect::TraitMessageInfo<{ ink::selector_id!($message) }>>::SELECTOR
+        };
+    }
+
+    /// Messages a paymaster only accepts from its forwarder, so transactions can never call them.
+    pub const PAYMASTER_VALIDATE_SELECTOR: MessageSelector =
+        trait_selector!(paymaster_trait::Paymaster, "validate");
+    pub const PAYMASTER_REIMBURSE_SELECTOR: MessageSelector =
+        trait_selector!(paymaster_trait::Paymaster, "reimburse");
+    /// Message a fee collector only accepts from its forwarder, so transactions can never call it.
+    pub const FEE_COLLECTOR_COLLECT_SELECTOR: MessageSelector =
+        trait_selector!(fee_collector_trait::FeeCollector, "collect");
+    /// Message the forwarder asks contract signers to approve signatures with.
+    pub const CONTRACT_SIGNER_IS_VALID_SIGNATURE_SELECTOR: MessageSelector =
+        trait_selector!(contract_signer::ContractSigner, "is_valid_signature");
+
+    /// Gas kept in reserve on top of a transaction's `gas_limit`, to finish `execute` after the call returns.
+    pub const GAS_SAFETY_MARGIN: u64 = 1_000_000_000;
+    /// Gas a contract signer may use to check a signature. Like the validation gas limit of ERC-4337 it keeps
+    /// a hostile signer from burning the relayer's gas, or that of a whole batch.
+    pub const CONTRACT_SIGNATURE_GAS_LIMIT: u64 = 2_000_000_000;
+
+    /// Signing context used by sr25519 signers, the same one polkadot.js and Substrate use.
+    pub const SR25519_SIGNING_CONTEXT: &[u8] = b"substrate";
+    /// Tags the polkadot.js extension wraps raw payloads in before signing them with `signRaw`.
+    pub const WRAPPED_BYTES_PREFIX: &[u8] = b"<Bytes>";
+    pub const WRAPPED_BYTES_SUFFIX: &[u8] = b"</Bytes>";
+
+    /// EIP-712 type of the domain. An `AccountId` does not fit the `verifyingContract` address,
+    /// so the forwarder address is committed to as the domain `salt` instead.
+    pub const EIP712_DOMAIN_TYPE: &str =
+        "EIP712Domain(string name,string version,uint256 chainId,bytes32 salt)";
+    /// EIP-712 type of [`Transaction`].
+    pub const EIP712_TRANSACTION_TYPE: &str = "Transaction(bytes32 from,bytes32 callee,bytes4 selector,bytes input,uint8 senderMode,bool returnsResult,uint128 transferredValue,uint128 tip,uint64 gasLimit,bool allowReentry,uint8 nonceMode,uint64 nonceKey,uint128 nonce,uint32 epoch,uint8 validityUnit,uint64 validAfter,uint64 validUntil,bytes32 feeToken,uint128 feeAmount,bytes32 feeRecipient,bytes32 paymaster,bytes32 relayer)";
+    /// EIP-712 type of [`CancelRequest`]. `kind` is the index of the [`Cancellation`] variant,
+    /// fields the variant does not have are zero.
+    pub const EIP712_CANCEL_TYPE: &str = "Cancel(bytes32 from,uint8 kind,uint8 nonceMode,uint64 nonceKey,uint128 nonce,uint32 epoch)";
+
+    /// How a [`Transaction`] is protected against replay.
+    #[derive(scale::Decode, scale::Encode, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(Debug, scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum NonceMode {
+        /// `nonce` must be the next nonce of the `nonce_key` sequence.
+        Sequential,
+        /// `nonce` is a bit in the signer's nonce bitmap that must not have been used yet.
+        /// Transactions can be executed in any order; `nonce_key` is ignored.
+        Unordered,
+    }
+
+    /// How the forwarder passes the signer of a [`Transaction`] on to the callee.
+    ///
+    /// In both modes the call input ends with the relayer and the signer as 32 raw bytes each, which
+    /// `MetaTxContext` reads with `_relayer`, `_msg_sender` and `_msg_data`.
+    #[derive(scale::Decode, scale::Encode, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(Debug, scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum SenderMode {
+        /// Also SCALE encoded as an extra `Vec<u8>` argument, for `MetaTxContext::_caller`. The signed input
+        /// could place a forged argument before it, so `_caller` checks it against the appended signer.
+        Argument,
+        /// Only appended to the input. Lets messages without a `data` argument be called, since ink!
+        /// ignores input bytes beyond the arguments.
+        Trailing,
+    }
+
+    /// What the validity window of a [`Transaction`] is measured in.
+    #[derive(scale::Decode, scale::Encode, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(Debug, scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum ValidityUnit {
+        /// Block timestamps, in milliseconds.
+        Timestamp,
+        /// Block numbers.
+        BlockNumber,
+    }
+
+    /// A fee in PSP22 tokens the signer pays to the relayer of a [`Transaction`].
+    #[derive(scale::Decode, scale::Encode, Clone)]
+    #[cfg_attr(
+        feature = "std",
+        derive(
+            Debug,
+            PartialEq,
+            Eq,
+            scale_info::TypeInfo,
+            ink::storage::traits::StorageLayout
+        )
+    )]
+    pub struct Fee {
+        /// The PSP22 token contract the fee is paid in.
+        pub token: AccountId,
+        /// The maximum fee the signer agreed to pay. The forwarder cannot price gas in `token`,
+        /// so the relayer is paid exactly this amount.
+        pub amount: Balance,
+        /// The account receiving the fee. `None` pays whichever relayer submits the transaction.
+        pub recipient: Option<AccountId>,
+    }
 
     #[derive(scale::Decode, scale::Encode, Clone)]
     #[cfg_attr(
@@ -40,17 +174,242 @@ mod forwarder {
         pub selector: [u8; 4],
         /// The SCALE encoded parameters that are passed to the called function.
         pub input: Vec<u8>,
-        /// The amount of chain balance that is transferred to the callee.
+        /// How the signer is passed on to the callee.
+        pub sender_mode: SenderMode,
+        /// Whether the called message returns a `Result`. Only then is an output starting with `0x01`, an `Err`,
+        /// reported as `CalleeReverted`.
+        pub returns_result: bool,
+        /// The amount of chain balance that is transferred to the callee, paid from the signer's deposit.
         pub transferred_value: Balance,
-        /// Gas limit for the execution of the call.
+        /// The amount of chain balance paid to the relayer from the signer's deposit after successful execution.
+        pub tip: Balance,
+        /// Gas limit for the execution of the call. Must not be zero, which ink! treats as all remaining gas.
         pub gas_limit: u64,
         /// If set to true the transaction will be allowed to re-enter the multisig contract.
         /// Re-entrancy can lead to vulnerabilities. Use at your own risk.
         pub allow_reentry: bool,
-        /// Submitted nonce. Must match what is expected on-chain or transaction is invalid.
+        /// Whether `nonce` is a sequential or an unordered nonce.
+        pub nonce_mode: NonceMode,
+        /// The nonce sequence `nonce` belongs to. Sequences progress independently of each other,
+        /// so transactions under different keys do not have to be executed in order.
+        pub nonce_key: NonceKey,
+        /// Submitted nonce. Must match what is expected on-chain for `nonce_key` or transaction is invalid.
         pub nonce: Nonce,
-        /// Transaction must be executed before this deadline or it becomes invalid
-        pub expiration_time_seconds: Timestamp,
+        /// Epoch of the signer the transaction was signed in. Must match the current epoch on-chain.
+        pub epoch: Epoch,
+        /// Unit of `valid_after` and `valid_until`.
+        pub validity_unit: ValidityUnit,
+        /// Transaction cannot be executed before this point in time (inclusive).
+        pub valid_after: u64,
+        /// Transaction must be executed before this deadline (exclusive) or it becomes invalid.
+        pub valid_until: u64,
+        /// Fee paid to the relayer after successful execution. The signer must have approved the forwarder's
+        /// fee collector to spend `fee.amount` of `fee.token`.
+        pub fee: Option<Fee>,
+        /// Paymaster sponsoring the transaction. It must approve the transaction before execution
+        /// and reimburses the relayer for the gas used afterwards.
+        pub paymaster: Option<AccountId>,
+        /// The only relayer allowed to submit the transaction. `None` lets any relayer submit it.
+        pub relayer: Option<AccountId>,
+    }
+
+    /// Signatures and nonces a signer wants to revoke.
+    #[derive(scale::Decode, scale::Encode, Clone, Copy)]
+    #[cfg_attr(feature = "std", derive(Debug, PartialEq, Eq, scale_info::TypeInfo))]
+    pub enum Cancellation {
+        /// Burn a single nonce. For sequential nonces this also burns every lower nonce of `nonce_key`.
+        Nonce {
+            nonce_mode: NonceMode,
+            nonce_key: NonceKey,
+            nonce: Nonce,
+        },
+        /// Burn every sequential nonce of `nonce_key` lower than `nonce`.
+        NoncesUpTo { nonce_key: NonceKey, nonce: Nonce },
+        /// Move on from `epoch`, which must be the current epoch, invalidating every outstanding signature.
+        Epoch { epoch: Epoch },
+    }
+
+    /// A cancellation signed by `from`, to be submitted by anyone through `cancel_by_signature`.
+    #[derive(scale::Decode, scale::Encode, Clone)]
+    #[cfg_attr(feature = "std", derive(Debug, PartialEq, Eq, scale_info::TypeInfo))]
+    pub struct CancelRequest {
+        /// The account whose nonces are cancelled. Should be the signer.
+        pub from: AccountId,
+        /// What to cancel.
+        pub cancellation: Cancellation,
+    }
+
+    /// A message signers sign under the forwarder's signing domain.
+    trait Signable: scale::Encode {
+        /// The account that must have signed the message.
+        fn signer(&self) -> AccountId;
+
+        /// EIP-712 `hashStruct` of the message.
+        fn eip712_struct_hash(&self) -> [u8; 32];
+    }
+
+    /// A signature over a [`Transaction`] or a [`CancelRequest`], tagged with the scheme that produced it.
+    #[derive(scale::Decode, scale::Encode, Clone)]
+    #[cfg_attr(feature = "std", derive(Debug, PartialEq, Eq, scale_info::TypeInfo))]
+    pub enum Signature {
+        /// ECDSA signature over `domain_separator ++ SCALE(transaction)`.
+        /// The signer is the blake2x256 hash of the compressed public key, as for Substrate ECDSA accounts.
+        Ecdsa([u8; 65]),
+        /// EIP-712 typed data signature, as produced by `eth_signTypedData_v4` in MetaMask.
+        /// The signer is the Ethereum address of the key, mapped to an `AccountId` as `blake2x256("evm:" ++ address)`.
+        Eip712([u8; 65]),
+        /// sr25519 signature over `domain_separator ++ SCALE(transaction)` in the `substrate` signing context,
+        /// either raw or wrapped in `<Bytes>..</Bytes>` as by the polkadot.js extension.
+        /// The public key is `transaction.from`.
+        Sr25519([u8; 64]),
+        /// ed25519 signature over `domain_separator ++ SCALE(transaction)`, either raw or wrapped in
+        /// `<Bytes>..</Bytes>` as by the polkadot.js extension.
+        /// The public key is `transaction.from`.
+        Ed25519([u8; 64]),
+        /// Signature of a contract `from`, such as a multisig wallet, which the forwarder asks to check it
+        /// with `ContractSigner::is_valid_signature` and `CONTRACT_SIGNATURE_GAS_LIMIT` gas. The hash to approve
+        /// is `blake2x256(domain_separator ++ SCALE(transaction))`, as signed with `Ecdsa`.
+        Contract(Vec<u8>),
+    }
+
+    impl Signable for Transaction {
+        fn signer(&self) -> AccountId {
+            self.from
+        }
+
+        fn eip712_struct_hash(&self) -> [u8; 32] {
+            let mut selector = [0; 32];
+            selector[..4].copy_from_slice(&self.selector);
+
+            let mut encoded = Vec::with_capacity(23 * 32);
+            encoded.extend_from_slice(&Forwarder::keccak256_hash(
+                EIP712_TRANSACTION_TYPE.as_bytes(),
+            ));
+            encoded.extend_from_slice(self.from.as_ref());
+            encoded.extend_from_slice(self.callee.as_ref());
+            encoded.extend_from_slice(&selector);
+            encoded.extend_from_slice(&Forwarder::keccak256_hash(&self.input));
+            encoded.extend_from_slice(&eip712_uint(self.sender_mode as u128));
+            encoded.extend_from_slice(&eip712_uint(self.returns_result.into()));
+            encoded.extend_from_slice(&eip712_uint(self.transferred_value));
+            encoded.extend_from_slice(&eip712_uint(self.tip));
+            encoded.extend_from_slice(&eip712_uint(self.gas_limit.into()));
+            encoded.extend_from_slice(&eip712_uint(self.allow_reentry.into()));
+            encoded.extend_from_slice(&eip712_uint(self.nonce_mode as u128));
+            encoded.extend_from_slice(&eip712_uint(self.nonce_key.into()));
+            encoded.extend_from_slice(&eip712_uint(self.nonce));
+            encoded.extend_from_slice(&eip712_uint(self.epoch.into()));
+            encoded.extend_from_slice(&eip712_uint(self.validity_unit as u128));
+            encoded.extend_from_slice(&eip712_uint(self.valid_after.into()));
+            encoded.extend_from_slice(&eip712_uint(self.valid_until.into()));
+            // A missing fee is encoded as zero words, a missing fee recipient as the zero account
+            let fee = self.fee.clone().unwrap_or(Fee {
+                token: AccountId::from([0; 32]),
+                amount: 0,
+                recipient: None,
+            });
+            encoded.extend_from_slice(fee.token.as_ref());
+            encoded.extend_from_slice(&eip712_uint(fee.amount));
+            encoded.extend_from_slice(
+                fee.recipient
+                    .unwrap_or_else(|| AccountId::from([0; 32]))
+                    .as_ref(),
+            );
+            encoded.extend_from_slice(
+                self.paymaster
+                    .unwrap_or_else(|| AccountId::from([0; 32]))
+                    .as_ref(),
+            );
+            encoded.extend_from_slice(
+                self.relayer
+                    .unwrap_or_else(|| AccountId::from([0; 32]))
+                    .as_ref(),
+            );
+            Forwarder::keccak256_hash(&encoded)
+        }
+    }
+
+    impl Signable for CancelRequest {
+        fn signer(&self) -> AccountId {
+            self.from
+        }
+
+        fn eip712_struct_hash(&self) -> [u8; 32] {
+            let (kind, nonce_mode, nonce_key, nonce, epoch) = match self.cancellation {
+                Cancellation::Nonce {
+                    nonce_mode,
+                    nonce_key,
+                    nonce,
+                } => (0, nonce_mode as u128, nonce_key, nonce, 0),
+                Cancellation::NoncesUpTo { nonce_key, nonce } => (1, 0, nonce_key, nonce, 0),
+                Cancellation::Epoch { epoch } => (2, 0, 0, 0, epoch),
+            };
+
+            let mut encoded = Vec::with_capacity(7 * 32);
+            encoded.extend_from_slice(&Forwarder::keccak256_hash(EIP712_CANCEL_TYPE.as_bytes()));
+            encoded.extend_from_slice(self.from.as_ref());
+            encoded.extend_from_slice(&eip712_uint(kind));
+            encoded.extend_from_slice(&eip712_uint(nonce_mode));
+            encoded.extend_from_slice(&eip712_uint(nonce_key.into()));
+            encoded.extend_from_slice(&eip712_uint(nonce));
+            encoded.extend_from_slice(&eip712_uint(epoch.into()));
+            Forwarder::keccak256_hash(&encoded)
+        }
+    }
+
+    /// Encode an unsigned integer as a 32 byte big-endian EIP-712 word.
+    fn eip712_uint(value: u128) -> [u8; 32] {
+        let mut word = [0; 32];
+        word[16..].copy_from_slice(&value.to_be_bytes());
+        word
+    }
+
+    /// How `execute_batch` handles a transaction of the batch that fails.
+    #[derive(scale::Decode, scale::Encode, Clone, Copy)]
+    #[cfg_attr(feature = "std", derive(Debug, PartialEq, Eq, scale_info::TypeInfo))]
+    pub enum BatchMode {
+        /// The whole batch fails, nothing is executed.
+        AllOrNothing,
+        /// A transaction failing before its call is skipped and consumes nothing. A transaction whose call
+        /// fails is reported as `CallOutcome::Failed`, as by `execute`.
+        /// Running out of gas or failing to pay the relayer still fails the whole batch.
+        BestEffort,
+    }
+
+    /// What the call of an executed transaction did.
+    ///
+    /// A returned `Err` reverts all changes of `execute`, so the failure of the call is returned as an `Ok`
+    /// outcome instead, which keeps the nonce used. Otherwise a failing transaction could be replayed later.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub enum CallOutcome {
+        /// The call succeeded. Contains the SCALE encoded return value of the called message
+        Succeeded(Vec<u8>),
+        /// The call failed with `CalleeReverted`, `CalleeDispatchFailed` or `TransactionFailed`. The nonce and the
+        /// paymaster quota are used up, but the value and tip stay in the signer's deposit and no fee is paid
+        Failed(Error),
+    }
+
+    /// Parameters that bind a signature to a single forwarder deployment on a single chain.
+    ///
+    /// The domain separator is the blake2x256 hash of the SCALE encoding of this struct.
+    #[derive(scale::Encode)]
+    struct Domain<'a> {
+        name: &'a str,
+        version: &'a str,
+        genesis_hash: Hash,
+        verifying_contract: AccountId,
+    }
+
+    /// Wrap `payload` in `<Bytes>..</Bytes>`, as the polkadot.js extension does before signing it.
+    fn wrap_bytes(payload: &[u8]) -> Vec<u8> {
+        let mut wrapped = Vec::with_capacity(
+            WRAPPED_BYTES_PREFIX.len() + payload.len() + WRAPPED_BYTES_SUFFIX.len(),
+        );
+        wrapped.extend_from_slice(WRAPPED_BYTES_PREFIX);
+        wrapped.extend_from_slice(payload);
+        wrapped.extend_from_slice(WRAPPED_BYTES_SUFFIX);
+        wrapped
     }
 
     /// A wrapper that allows us to encode a blob of bytes.
@@ -64,6 +423,69 @@ mod forwarder {
         }
     }
 
+    /// A wrapper that allows us to decode a blob of bytes.
+    ///
+    /// We use this to receive the untyped (bytes) return value of the called message.
+    struct CallOutput(Vec<u8>);
+
+    impl scale::Decode for CallOutput {
+        fn decode<I: scale::Input>(input: &mut I) -> Result<Self, scale::Error> {
+            let len = input
+                .remaining_len()?
+                .ok_or("length of the call output is unknown")?;
+            let mut output = vec![0; len];
+            input.read(&mut output)?;
+            Ok(Self(output))
+        }
+    }
+
+    /// The input of the call `req` makes after its selector: the signed input, the signer as an argument
+    /// if requested, then `relayer` and the signer as raw bytes.
+    fn call_input(req: &Transaction, relayer: AccountId) -> Vec<u8> {
+        let mut input = req.input.clone();
+        if let SenderMode::Argument = req.sender_mode {
+            req.from.encode().encode_to(&mut input);
+        }
+        input.extend_from_slice(relayer.as_ref());
+        input.extend_from_slice(req.from.as_ref());
+        input
+    }
+
+    /// Check that `gas_left` is enough to give the callee the full `gas_limit` the signer asked for.
+    ///
+    /// Otherwise a relayer could make the call run out of gas on purpose while the nonce is consumed,
+    /// burning the signed transaction (the EIP-150 gas griefing issue).
+    fn check_gas_left(gas_left: u64, gas_limit: u64) -> Result<(), Error> {
+        match gas_limit.checked_add(GAS_SAFETY_MARGIN) {
+            Some(required_gas) if gas_left >= required_gas => Ok(()),
+            _ => Err(Error::InsufficientGas),
+        }
+    }
+
+    /// Turn the result of calling the callee of a transaction into the result of `execute`.
+    ///
+    /// ink! 4 does not expose the callee's return flags to the caller, so a reverted callee is recognised
+    /// by ink!'s ABI instead: a message reverts exactly when it returns `Err`, whose encoding starts with `0x01`.
+    /// Other return types such as `bool` or `Option` can start with the same byte, so the output is only
+    /// checked if the signer declared that the message `returns_result`.
+    fn call_result(
+        result: Result<ink::MessageResult<CallOutput>, ink::env::Error>,
+        returns_result: bool,
+    ) -> Result<Vec<u8>, Error> {
+        match result {
+            Ok(Ok(CallOutput(output))) => {
+                match output.split_first() {
+                    Some((1, error)) if returns_result => {
+                        Err(Error::CalleeReverted(error.to_vec()))
+                    }
+                    _ => Ok(output),
+                }
+            }
+            Ok(Err(lang_error)) => Err(Error::CalleeDispatchFailed(lang_error)),
+            Err(_) => Err(Error::TransactionFailed),
+        }
+    }
+
     /// A meta-transaction was executed
     #[ink(event)]
     pub struct Executed {
@@ -74,143 +496,2599 @@ mod forwarder {
         encoded_transaction: Transaction,
     }
 
+    /// Unordered nonces of `owner` were invalidated
+    #[ink(event)]
+    pub struct UnorderedNonceInvalidation {
+        #[ink(topic)]
+        owner: AccountId,
+        word_position: u128,
+        mask: u128,
+    }
+
+    /// A nonce of `owner` was cancelled
+    #[ink(event)]
+    pub struct NonceCancelled {
+        #[ink(topic)]
+        owner: AccountId,
+        nonce_mode: NonceMode,
+        nonce_key: NonceKey,
+        nonce: Nonce,
+    }
+
+    /// Every sequential nonce of `owner` under `nonce_key` lower than `nonce` was invalidated
+    #[ink(event)]
+    pub struct NoncesInvalidated {
+        #[ink(topic)]
+        owner: AccountId,
+        nonce_key: NonceKey,
+        nonce: Nonce,
+    }
+
+    /// `owner` moved to a new epoch, invalidating every signature made for earlier epochs
+    #[ink(event)]
+    pub struct EpochIncremented {
+        #[ink(topic)]
+        owner: AccountId,
+        epoch: Epoch,
+    }
+
+    /// The forwarder was paused by `account`
+    #[ink(event)]
+    pub struct Paused {
+        account: AccountId,
+    }
+
+    /// The forwarder was unpaused by `account`
+    #[ink(event)]
+    pub struct Unpaused {
+        account: AccountId,
+    }
+
+    /// `owner` deposited `amount` to fund its transactions
+    #[ink(event)]
+    pub struct Deposited {
+        #[ink(topic)]
+        owner: AccountId,
+        amount: Balance,
+    }
+
+    /// `owner` withdrew `amount` from its deposit
+    #[ink(event)]
+    pub struct Withdrawn {
+        #[ink(topic)]
+        owner: AccountId,
+        amount: Balance,
+    }
+
     /// Errors that can occur upon calling this contract.
-    #[derive(Copy, Clone, Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
     pub enum Error {
         /// Signature does not match transaction
         BadSignature,
-        /// Returned if the call failed.
+        /// Returned if the call failed, e.g. because the callee trapped or ran out of gas.
         TransactionFailed,
-        /// Token amount specified in transaction did not match tokens sent with `execute()` call
-        ValueTransferMismatch,
-        /// Transaction submitted cannot be executed after it has expired (transaction.expiration_time_seconds)
+        /// The signer's deposit does not cover the transferred value and tip of the transaction,
+        /// or a withdrawal exceeds the deposit
+        InsufficientDeposit,
+        /// Transaction submitted cannot be executed after it has expired (transaction.valid_until)
         TransactionExpired,
         // Submitted nonce does match expected
         IncorrectNonce,
         // Signature does not match transaction
         IncorrectSignature,
+        /// Submitted unordered nonce has already been used or invalidated
+        NonceAlreadyUsed,
+        /// The transaction at the given index of an `AllOrNothing` batch failed, or its call did
+        BatchItemFailed(u32),
+        /// Submitted epoch does not match the signer's current epoch
+        IncorrectEpoch,
+        /// Transaction submitted cannot be executed before its validity window opens (transaction.valid_after)
+        TransactionNotYetValid,
+        /// The callee could not dispatch the call, e.g. because it has no message with the selector
+        CalleeDispatchFailed(ink::LangError),
+        /// The callee returned an `Err` and reverted. Contains the SCALE encoded error
+        CalleeReverted(Vec<u8>),
+        /// Not enough gas was provided to run the call with the transaction's `gas_limit` plus `GAS_SAFETY_MARGIN`
+        InsufficientGas,
+        /// The transaction's `gas_limit` is zero, which would let the call use whatever gas the relayer provides
+        ZeroGasLimit,
+        /// The fee could not be transferred from the signer to the relayer, or no fee collector is set
+        FeePaymentFailed,
+        /// The tip could not be transferred to the relayer
+        TipPaymentFailed,
+        /// The paymaster refused to sponsor the transaction
+        PaymasterRejected,
+        /// The paymaster could not reimburse the relayer
+        PaymasterReimbursementFailed,
+        /// The forwarder is permissioned and the caller does not have the `RELAYER` role
+        RelayerNotAllowed,
+        /// The transaction is pinned to another relayer
+        IncorrectRelayer,
+        /// The callee or its selector is not allowed by the forwarder's policy, or is reserved for the forwarder,
+        /// or the callee is the transaction's paymaster
+        CalleeNotAllowed,
+        /// The forwarder is paused
+        Paused,
+        /// The withdrawn amount could not be transferred to the owner
+        WithdrawalFailed,
+        /// The code of the forwarder could not be replaced, e.g. because no code with the hash was uploaded
+        UpgradeFailed,
+        /// The storage was written by another version of the forwarder and has not been migrated to `STORAGE_VERSION`
+        MigrationPending,
+        /// The storage was written by a newer version of the forwarder or is not in the layout of its version,
+        /// so this code cannot convert it
+        UnsupportedStorageVersion,
+        AccessControlError(AccessControlError),
+    }
+
+    impl From<AccessControlError> for Error {
+        fn from(err: AccessControlError) -> Self {
+            Error::AccessControlError(err)
+        }
     }
 
+    /// Storage key of [`NonceData`].
+    pub const NONCE_STORAGE_KEY: u32 = openbrush::storage_unique_key!(NonceData);
+    /// Storage key of [`DepositData`].
+    pub const DEPOSIT_STORAGE_KEY: u32 = openbrush::storage_unique_key!(DepositData);
+    /// Storage key of [`FeeCollectorData`].
+    pub const FEE_COLLECTOR_STORAGE_KEY: u32 = openbrush::storage_unique_key!(FeeCollectorData);
+    /// Storage key of [`ConfigData`].
+    pub const CONFIG_STORAGE_KEY: u32 = openbrush::storage_unique_key!(ConfigData);
+
+    /// Version of the storage layout written by this code, see [`Forwarder::migrate`].
+    ///
+    /// - `1`: the configuration is packed into the root cell of [`Forwarder`], see [`ConfigV1`].
+    /// - `2`: the configuration lives in [`ConfigData`] under its own fixed key.
+    pub const STORAGE_VERSION: u32 = 2;
+
+    /// Replay protection state of every signer.
+    ///
+    /// It lives under a fixed storage key and its layout must never change, so that upgrades
+    /// cannot reset nonces and reopen the replay window of old signatures.
+    #[openbrush::upgradeable_storage(NONCE_STORAGE_KEY)]
+    #[derive(Default, Debug)]
+    pub struct NonceData {
+        /// Not a 256 bit integer as in the solidity version, but putting highest rust integer for now
+        pub nonces: Mapping<(AccountId, NonceKey), Nonce>,
+        /// Bitmaps of used unordered nonces, 128 nonces per word, keyed by owner and word position.
+        pub nonce_bitmaps: Mapping<(AccountId, u128), u128>,
+        /// Current epoch of every signer.
+        pub epochs: Mapping<AccountId, Epoch>,
+    }
+
+    /// Native balance every signer deposited to fund the value and tips of its transactions.
+    ///
+    /// Like [`NonceData`] it lives under a fixed storage key, so upgrades cannot lose the deposits.
+    #[openbrush::upgradeable_storage(DEPOSIT_STORAGE_KEY)]
+    #[derive(Default, Debug)]
+    pub struct DepositData {
+        pub deposits: Mapping<AccountId, Balance>,
+    }
+
+    /// The fee collector of the forwarder, added after the first upgradeable version.
+    ///
+    /// It lives under its own fixed storage key rather than in the packed fields of [`Forwarder`], so storage
+    /// written by that version decodes as before and simply has no fee collector yet.
+    #[openbrush::upgradeable_storage(FEE_COLLECTOR_STORAGE_KEY)]
+    #[derive(Default, Debug)]
+    pub struct FeeCollectorData {
+        /// Contract collecting fees from the signers, who approve it instead of the forwarder.
+        pub fee_collector: Option<AccountId>,
+    }
+
+    /// Settings of the forwarder and the version of the storage layout.
+    ///
+    /// It lives under a fixed storage key, so it can still be read after an upgrade changed [`Forwarder`].
+    /// A version changing these fields must move them to a new struct under a new key and convert them in
+    /// `migrate`, since this one is decoded by position.
+    #[openbrush::upgradeable_storage(CONFIG_STORAGE_KEY)]
+    #[derive(Default, Debug)]
+    pub struct ConfigData {
+        /// Version of the storage layout, `STORAGE_VERSION` once migrated. Zero while the configuration is still
+        /// packed into the root cell by version 1.
+        pub version: u32,
+        /// Genesis hash of the chain this forwarder is deployed on, part of the signing domain.
+        pub genesis_hash: Hash,
+        /// EVM chain id of the chain this forwarder is deployed on, part of the EIP-712 domain.
+        pub evm_chain_id: u64,
+        /// Whether only accounts with the `RELAYER` role may submit transactions.
+        pub permissioned: bool,
+        /// Whether execution is halted.
+        pub paused: bool,
+        /// Whether only allowed callees and selectors may be called. Any callee may be called otherwise.
+        pub callees_restricted: bool,
+    }
+
+    /// The configuration version 1 packed into the root cell of [`Forwarder`], after its storage fields.
+    #[derive(scale::Decode, scale::Encode)]
+    pub struct ConfigV1 {
+        pub genesis_hash: Hash,
+        pub evm_chain_id: u64,
+        pub storage_version: u32,
+        pub permissioned: bool,
+        pub paused: bool,
+        pub callees_restricted: bool,
+    }
+
+    /// The rest of the root cell, kept as raw bytes.
+    ///
+    /// It decodes whatever the other fields of [`Forwarder`] left of the cell and encodes it back unchanged, so
+    /// the [`ConfigV1`] of an upgraded forwarder survives the calls made before `migrate` converts it.
+    #[derive(Default, Debug)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct LegacyCell(Vec<u8>);
+
+    impl scale::Encode for LegacyCell {
+        fn encode_to<T: Output + ?Sized>(&self, dest: &mut T) {
+            dest.write(&self.0);
+        }
+    }
+
+    impl scale::Decode for LegacyCell {
+        fn decode<I: scale::Input>(input: &mut I) -> Result<Self, scale::Error> {
+            let len = input
+                .remaining_len()?
+                .ok_or("length of the root cell is unknown")?;
+            let mut bytes = vec![0; len];
+            input.read(&mut bytes)?;
+            Ok(Self(bytes))
+        }
+    }
+
+    /// The forwarder can be upgraded in place with `set_code_hash`.
+    ///
+    /// Its state lives in storage structs under their own fixed key, whose layout new code must keep or
+    /// convert in `migrate`, see [`ConfigData`]. The mappings below are keyed by their field names, which
+    /// must not change either. Nothing but `legacy_config` is packed into the root cell.
     #[ink(storage)]
+    #[derive(Storage)]
     pub struct Forwarder {
-        /// Not a 256 bit integer as in the solidity version, but putting highest rust integer for now
-        nonces: Mapping<AccountId, Nonce>,
+        #[storage_field]
+        access_control: access_control::Data,
+
+        #[storage_field]
+        nonce_data: NonceData,
+        #[storage_field]
+        deposit_data: DepositData,
+        #[storage_field]
+        fee_collector_data: FeeCollectorData,
+        #[storage_field]
+        config: ConfigData,
+        /// Callees whose every message may be called while callees are restricted.
+        allowed_callees: Mapping<AccountId, ()>,
+        /// Messages that may be called while callees are restricted, keyed by callee and selector.
+        allowed_selectors: Mapping<(AccountId, MessageSelector), ()>,
+        /// The [`ConfigV1`] of a forwarder upgraded from version 1, empty once migrated.
+        legacy_config: LegacyCell,
     }
 
+    impl AccessControl for Forwarder {}
+
     impl Forwarder {
+        /// Creates an open forwarder for the chain identified by `genesis_hash` and `evm_chain_id`,
+        /// administered by the caller.
         #[ink(constructor)]
-        pub fn default() -> Self {
-            Self {
-                nonces: Mapping::default(),
+        pub fn new(genesis_hash: Hash, evm_chain_id: u64) -> Self {
+            let mut _instance = Self {
+                access_control: Default::default(),
+                nonce_data: NonceData::default(),
+                deposit_data: DepositData::default(),
+                fee_collector_data: FeeCollectorData::default(),
+                config: ConfigData {
+                    version: STORAGE_VERSION,
+                    genesis_hash,
+                    evm_chain_id,
+                    permissioned: false,
+                    paused: false,
+                    callees_restricted: false,
+                },
+                allowed_callees: Mapping::default(),
+                allowed_selectors: Mapping::default(),
+                legacy_config: LegacyCell::default(),
+            };
+            _instance._init_with_admin(_instance.env().caller());
+            _instance._setup_role(GUARDIAN, _instance.env().caller());
+            _instance
+        }
+
+        /// Replace the code of the forwarder, keeping its storage. Call `migrate` on the new code afterwards. Admin only.
+        #[ink(message)]
+        #[modifiers(only_role(DEFAULT_ADMIN_ROLE))]
+        pub fn set_code_hash(&mut self, code_hash: [u8; 32]) -> Result<(), Error> {
+            ink::env::set_code_hash(&code_hash).map_err(|_| Error::UpgradeFailed)
+        }
+
+        /// Returns the version of the storage layout.
+        #[ink(message)]
+        pub fn storage_version(&self) -> u32 {
+            match self.config.version {
+                // The configuration is still where version 1 kept it
+                0 => 1,
+                version => version,
             }
         }
 
-        /// Get the nonce for the given account
+        /// Convert storage written by an older version of the forwarder to `STORAGE_VERSION`, one version
+        /// at a time. Execution is halted until it has been called. Admin only.
         #[ink(message)]
-        pub fn get_nonce(&self, address: AccountId) -> Nonce {
-            self.nonces.get(address).unwrap_or(0 as Nonce)
+        #[modifiers(only_role(DEFAULT_ADMIN_ROLE))]
+        pub fn migrate(&mut self) -> Result<(), Error> {
+            if self.storage_version() == 1 {
+                self.migrate_from_v1()?;
+            }
+            if self.storage_version() != STORAGE_VERSION {
+                return Err(Error::UnsupportedStorageVersion)
+            }
+            Ok(())
         }
 
-        /// Verifies that a transaction matches its supplied signature.
+        /// Move the [`ConfigV1`] out of the root cell into [`ConfigData`].
+        fn migrate_from_v1(&mut self) -> Result<(), Error> {
+            let legacy = ConfigV1::decode(&mut &self.legacy_config.0[..])
+                .map_err(|_| Error::UnsupportedStorageVersion)?;
+            if legacy.storage_version != 1 {
+                return Err(Error::UnsupportedStorageVersion)
+            }
+            self.config = ConfigData {
+                version: 2,
+                genesis_hash: legacy.genesis_hash,
+                evm_chain_id: legacy.evm_chain_id,
+                permissioned: legacy.permissioned,
+                // A guardian may have paused the forwarder since the upgrade
+                paused: legacy.paused || self.config.paused,
+                callees_restricted: legacy.callees_restricted,
+            };
+            self.legacy_config = LegacyCell::default();
+            Ok(())
+        }
+
+        /// Assert that the storage has the layout this code expects.
+        fn ensure_migrated(&self) -> Result<(), Error> {
+            if self.config.version != STORAGE_VERSION {
+                return Err(Error::MigrationPending)
+            }
+            Ok(())
+        }
+
+        /// Returns whether execution is halted.
         #[ink(message)]
-        pub fn verfiy(&self, req: Transaction, signature: [u8; 65]) -> Result<(), Error> {
-            let encoded_msg: Vec<u8> = req.encode();
-            let message_hash = Self::blake2x256_hash(encoded_msg);
-            match self.env().ecdsa_recover(&signature, &message_hash) {
-                Ok(pub_key) => {
-                    // Match recovered pub_key with caller
-                    let caller = req.from;
-                    let signer = Self::to_default_account_id(pub_key);
-                    let expected_nonce = self.get_nonce(caller);
-
-                    // Is the message signed by the same account that sent it?
-                    // And does the transacation have the expected nonce?
-                    if expected_nonce != req.nonce {
-                        return Err(Error::IncorrectNonce)
-                    }
+        pub fn paused(&self) -> bool {
+            self.config.paused
+        }
 
-                    if signer != caller {
-                        return Err(Error::IncorrectSignature)
-                    }
+        /// Halt `execute` and `execute_batch`. Guardian only.
+        #[ink(message)]
+        #[modifiers(only_role(GUARDIAN))]
+        pub fn pause(&mut self) -> Result<(), AccessControlError> {
+            self.config.paused = true;
+            self.env().emit_event(Paused {
+                account: self.env().caller(),
+            });
+            Ok(())
+        }
+
+        /// Resume execution. Admin only.
+        #[ink(message)]
+        #[modifiers(only_role(DEFAULT_ADMIN_ROLE))]
+        pub fn unpause(&mut self) -> Result<(), AccessControlError> {
+            self.config.paused = false;
+            self.env().emit_event(Unpaused {
+                account: self.env().caller(),
+            });
+            Ok(())
+        }
+
+        /// Returns whether only accounts with the `RELAYER` role may submit transactions.
+        #[ink(message)]
+        pub fn is_permissioned(&self) -> bool {
+            self.config.permissioned
+        }
+
+        /// Switch between open and permissioned mode. Relayers are added and removed by granting
+        /// and revoking the `RELAYER` role. Admin only.
+        #[ink(message)]
+        #[modifiers(only_role(DEFAULT_ADMIN_ROLE))]
+        pub fn set_permissioned(&mut self, permissioned: bool) -> Result<(), AccessControlError> {
+            self.config.permissioned = permissioned;
+            Ok(())
+        }
+
+        /// Returns whether only allowed callees and selectors may be called.
+        #[ink(message)]
+        pub fn is_callees_restricted(&self) -> bool {
+            self.config.callees_restricted
+        }
 
-                    Ok(())
+        /// Switch between restricting calls to allowed callees and selectors and allowing any callee. Admin only.
+        #[ink(message)]
+        #[modifiers(only_role(DEFAULT_ADMIN_ROLE))]
+        pub fn set_callees_restricted(
+            &mut self,
+            restricted: bool,
+        ) -> Result<(), AccessControlError> {
+            self.config.callees_restricted = restricted;
+            Ok(())
+        }
+
+        /// Allow or disallow calls to `selector` of `callee`, or to every message of `callee` if `selector` is `None`.
+        /// Only takes effect while callees are restricted. Admin only.
+        #[ink(message)]
+        #[modifiers(only_role(DEFAULT_ADMIN_ROLE))]
+        pub fn set_callee_allowed(
+            &mut self,
+            callee: AccountId,
+            selector: Option<MessageSelector>,
+            allowed: bool,
+        ) -> Result<(), AccessControlError> {
+            match (selector, allowed) {
+                (Some(selector), true) => {
+                    self.allowed_selectors.insert((callee, selector), &());
                 }
-                Err(_) => Err(Error::IncorrectSignature),
+                (Some(selector), false) => self.allowed_selectors.remove((callee, selector)),
+                (None, true) => {
+                    self.allowed_callees.insert(callee, &());
+                }
+                (None, false) => self.allowed_callees.remove(callee),
             }
+            Ok(())
         }
 
-        /// Call a meta transaction. The transaction calling this fn must contain a valid transaction inside it that is passed to this fn as `req`.
-        /// `req` will be verified by `verify` using the supplied `signature`. If the transaction is signed correctly, it will be executed.
-        #[ink(message, payable)]
-        pub fn execute(&mut self, req: Transaction, signature: [u8; 65]) -> Result<(), Error> {
-            // Signature must be correct
-            self.verfiy(req.clone(), signature.clone())?;
+        /// Set the contract collecting fees on behalf of the forwarder, see `fee_collector`. Admin only.
+        #[ink(message)]
+        #[modifiers(only_role(DEFAULT_ADMIN_ROLE))]
+        pub fn set_fee_collector(
+            &mut self,
+            fee_collector: Option<AccountId>,
+        ) -> Result<(), AccessControlError> {
+            self.fee_collector_data.fee_collector = fee_collector;
+            Ok(())
+        }
 
-            // Assert that the correct amount of tokens were sent to this contract instance with this fn call
-            if self.env().transferred_value() != req.transferred_value {
-                return Err(Error::ValueTransferMismatch)
-            }
+        #[ink(message)]
+        pub fn get_fee_collector(&self) -> Option<AccountId> {
+            self.fee_collector_data.fee_collector
+        }
 
-            // Assert that the transaction hasn't already expired
-            if self.env().block_timestamp() >= req.expiration_time_seconds {
-                return Err(Error::TransactionExpired)
+        /// Returns whether transactions may call `selector` of `callee`. Messages reserved for the forwarder,
+        /// see `is_reserved`, can never be called, whatever the policy.
+        #[ink(message)]
+        pub fn is_callee_allowed(&self, callee: AccountId, selector: MessageSelector) -> bool {
+            if self.is_reserved(callee, selector) {
+                return false
             }
+            !self.config.callees_restricted
+                || self.allowed_callees.contains(callee)
+                || self.allowed_selectors.contains((callee, selector))
+        }
 
-            let caller = req.from;
-            let updated_nonce = self.get_nonce(caller) + 1;
+        /// Whether `selector` of `callee` trusts calls from the forwarder: every message of the forwarder
+        /// itself and of its fee collector, and the `Paymaster` and `FeeCollector` trait messages, which only
+        /// accept calls from their forwarder. Any transaction could drain their budgets and allowances otherwise.
+        /// Messages of other contracts merely named like them, such as a `collect`, can still be called.
+        fn is_reserved(&self, callee: AccountId, selector: MessageSelector) -> bool {
+            callee == self.env().account_id()
+                || Some(callee) == self.get_fee_collector()
+                || selector == PAYMASTER_VALIDATE_SELECTOR
+                || selector == PAYMASTER_REIMBURSE_SELECTOR
+                || selector == FEE_COLLECTOR_COLLECT_SELECTOR
+        }
 
-            // Signature is valid, so increase nonce and then execute transaction
-            self.nonces.insert(caller, &updated_nonce);
+        /// Check that `req` calls an allowed callee other than its paymaster, which trusts the forwarder.
+        /// The fee token may be called, the forwarder holds no allowances to spend on it.
+        fn check_callee(&self, req: &Transaction) -> Result<(), Error> {
+            if !self.is_callee_allowed(req.callee, req.selector)
+                || Some(req.callee) == req.paymaster
+            {
+                return Err(Error::CalleeNotAllowed)
+            }
+            Ok(())
+        }
 
-            // Run the transaction
-            let result = build_call::<<Self as ::ink::env::ContractEnv>::Env>()
-                .call(req.callee)
-                .gas_limit(req.gas_limit)
-                .transferred_value(req.transferred_value)
-                .call_flags(CallFlags::default().set_allow_reentry(req.allow_reentry))
-                .exec_input(
-                    // Add signer's account_id as extra input bytes
-                    // SCALE encoded
-                    ExecutionInput::new(req.selector.into())
-                        .push_arg(CallInput(&req.input))
-                        .push_arg(req.from.encode()),
-                )
-                .returns::<()>()
-                .try_invoke();
+        /// Get the nonce for the given account under the default nonce key `0`
+        #[ink(message)]
+        pub fn get_nonce(&self, address: AccountId) -> Nonce {
+            self.get_keyed_nonce(address, 0)
+        }
 
-            match result {
-                Ok(Ok(_)) => {
-                    self.env().emit_event(Executed {
-                        caller,
-                        callee: req.callee,
-                        encoded_transaction: req,
-                    });
-                    Ok(())
-                }
-                Err(_) => Err(Error::TransactionFailed),
-                _ => Err(Error::TransactionFailed),
-            }
+        /// Get the nonce for the given account under `key`
+        #[ink(message)]
+        pub fn get_keyed_nonce(&self, address: AccountId, key: NonceKey) -> Nonce {
+            self.nonce_data
+                .nonces
+                .get((address, key))
+                .unwrap_or(0 as Nonce)
         }
 
-        /// Convert a compressed 33 byte ECDSA public key into a 32 byte Substrate address
-        fn to_default_account_id(compressed_pub_key: [u8; 33]) -> ink::primitives::AccountId {
-            use ink::env::hash;
+        /// Get the word of `owner`'s unordered nonce bitmap at `word_position`.
+        /// Bit `n` of word `w` is set if nonce `w * 128 + n` has been used.
+        #[ink(message)]
+        pub fn nonce_bitmap(&self, owner: AccountId, word_position: u128) -> u128 {
+            self.nonce_data
+                .nonce_bitmaps
+                .get((owner, word_position))
+                .unwrap_or_default()
+        }
 
-            let mut output = <hash::Blake2x256 as hash::HashOutput>::Type::default();
-            ink::env::hash_bytes::<hash::Blake2x256>(&compressed_pub_key[..], &mut output);
+        /// Check whether `owner` has used or invalidated the unordered `nonce`.
+        #[ink(message)]
+        pub fn is_nonce_used(&self, owner: AccountId, nonce: Nonce) -> bool {
+            let (word_position, bit) = Self::bitmap_position(nonce);
+            self.nonce_bitmap(owner, word_position) & bit != 0
+        }
 
-            output.into()
+        /// Invalidate the unordered nonces of the caller whose bits are set in `mask`,
+        /// within the bitmap word at `word_position`.
+        #[ink(message)]
+        pub fn invalidate_unordered_nonces(&mut self, word_position: u128, mask: u128) {
+            let owner = self.env().caller();
+            let word = self.nonce_bitmap(owner, word_position) | mask;
+            self.nonce_data
+                .nonce_bitmaps
+                .insert((owner, word_position), &word);
+            self.env().emit_event(UnorderedNonceInvalidation {
+                owner,
+                word_position,
+                mask,
+            });
         }
 
-        /// Compute the 32 byte Blake 256 hash of the supplied byte vector.
-        fn blake2x256_hash(bytes: Vec<u8>) -> [u8; 32] {
-            use ink::env::hash;
+        /// Get the current epoch of the given account
+        #[ink(message)]
+        pub fn get_epoch(&self, address: AccountId) -> Epoch {
+            self.nonce_data.epochs.get(address).unwrap_or_default()
+        }
 
-            let mut output = <hash::Blake2x256 as hash::HashOutput>::Type::default();
-            ink::env::hash_bytes::<hash::Blake2x256>(&bytes[..], &mut output);
+        /// Cancel nonces or the current epoch of the caller.
+        #[ink(message)]
+        pub fn cancel(&mut self, cancellation: Cancellation) -> Result<(), Error> {
+            self.apply_cancellation(self.env().caller(), cancellation)
+        }
 
-            output
+        /// Cancel nonces or the current epoch of `req.from`, who signed the request.
+        /// Can be submitted by anyone, so signers do not need funds to revoke their signatures.
+        #[ink(message)]
+        pub fn cancel_by_signature(
+            &mut self,
+            req: CancelRequest,
+            signature: Signature,
+        ) -> Result<(), Error> {
+            self.verify_signature(&req, &signature)?;
+            self.apply_cancellation(req.from, req.cancellation)
+        }
+
+        /// Invalidate every sequential nonce of the caller under `nonce_key` lower than `nonce`.
+        #[ink(message)]
+        pub fn invalidate_nonces_up_to(
+            &mut self,
+            nonce_key: NonceKey,
+            nonce: Nonce,
+        ) -> Result<(), Error> {
+            self.cancel(Cancellation::NoncesUpTo { nonce_key, nonce })
+        }
+
+        /// Move the caller to the next epoch, invalidating all of their outstanding signatures.
+        /// Returns the new epoch.
+        #[ink(message)]
+        pub fn increment_epoch(&mut self) -> Result<Epoch, Error> {
+            let owner = self.env().caller();
+            let epoch = self.get_epoch(owner);
+            self.apply_cancellation(owner, Cancellation::Epoch { epoch })?;
+            Ok(self.get_epoch(owner))
+        }
+
+        /// Get the domain separator that is prepended to every signed message.
+        ///
+        /// Signers sign `domain_separator ++ SCALE(transaction)`, or `domain_separator ++ SCALE(cancel_request)`.
+        #[ink(message)]
+        pub fn domain_separator(&self) -> [u8; 32] {
+            let domain = Domain {
+                name: DOMAIN_NAME,
+                version: DOMAIN_VERSION,
+                genesis_hash: self.config.genesis_hash,
+                verifying_contract: self.env().account_id(),
+            };
+            Self::blake2x256_hash(domain.encode())
+        }
+
+        /// Get the EIP-712 domain separator, i.e. `hashStruct(EIP712Domain)` for
+        /// `{ name: DOMAIN_NAME, version: DOMAIN_VERSION, chainId: evm_chain_id, salt: forwarder_address }`.
+        #[ink(message)]
+        pub fn eip712_domain_separator(&self) -> [u8; 32] {
+            let mut encoded = Vec::with_capacity(5 * 32);
+            encoded.extend_from_slice(&Self::keccak256_hash(EIP712_DOMAIN_TYPE.as_bytes()));
+            encoded.extend_from_slice(&Self::keccak256_hash(DOMAIN_NAME.as_bytes()));
+            encoded.extend_from_slice(&Self::keccak256_hash(DOMAIN_VERSION.as_bytes()));
+            encoded.extend_from_slice(&eip712_uint(self.config.evm_chain_id.into()));
+            encoded.extend_from_slice(self.env().account_id().as_ref());
+            Self::keccak256_hash(&encoded)
+        }
+
+        /// Verifies that a transaction matches its supplied signature.
+        #[ink(message)]
+        pub fn verfiy(&self, req: Transaction, signature: Signature) -> Result<(), Error> {
+            // Does the transacation have the expected nonce?
+            self.check_nonce(&req)?;
+
+            // Was the transaction signed in the current epoch?
+            if self.get_epoch(req.from) != req.epoch {
+                return Err(Error::IncorrectEpoch)
+            }
+
+            // Is the message signed by the same account that sent it?
+            self.verify_signature(&req, &signature)
+        }
+
+        /// Checks whether the caller could execute a transaction right now: it must be correctly signed,
+        /// within its validity window, call an allowed callee and not be pinned to another relayer.
+        #[ink(message)]
+        pub fn is_executable(&self, req: Transaction, signature: Signature) -> Result<(), Error> {
+            self.check_relayer()?;
+            if let Some(relayer) = req.relayer {
+                if relayer != self.env().caller() {
+                    return Err(Error::IncorrectRelayer)
+                }
+            }
+            self.check_callee(&req)?;
+            // A zero gas limit leaves the callee's gas up to the relayer, defeating `check_gas_left`
+            if req.gas_limit == 0 {
+                return Err(Error::ZeroGasLimit)
+            }
+
+            // Assert that the transaction is within its validity window
+            let now = match req.validity_unit {
+                ValidityUnit::Timestamp => self.env().block_timestamp(),
+                ValidityUnit::BlockNumber => self.env().block_number().into(),
+            };
+            if now < req.valid_after {
+                return Err(Error::TransactionNotYetValid)
+            }
+            if now >= req.valid_until {
+                return Err(Error::TransactionExpired)
+            }
+
+            // Signature must be correct
+            self.verfiy(req, signature)
+        }
+
+        /// Returns the native balance `owner` has deposited to fund its transactions.
+        #[ink(message)]
+        pub fn balance_of(&self, owner: AccountId) -> Balance {
+            self.deposit_data.deposits.get(owner).unwrap_or(0)
+        }
+
+        /// Add the tokens sent with this call to the caller's deposit.
+        #[ink(message, payable)]
+        pub fn deposit(&mut self) {
+            let owner = self.env().caller();
+            let amount = self.env().transferred_value();
+            // Cannot overflow, the deposits never exceed the total issuance
+            self.deposit_data
+                .deposits
+                .insert(owner, &(self.balance_of(owner) + amount));
+            self.env().emit_event(Deposited { owner, amount });
+        }
+
+        /// Withdraw `amount` from the caller's deposit.
+        #[ink(message)]
+        pub fn withdraw(&mut self, amount: Balance) -> Result<(), Error> {
+            let owner = self.env().caller();
+            let balance = self.balance_of(owner);
+            if balance < amount {
+                return Err(Error::InsufficientDeposit)
+            }
+
+            self.deposit_data
+                .deposits
+                .insert(owner, &(balance - amount));
+            self.env()
+                .transfer(owner, amount)
+                .map_err(|_| Error::WithdrawalFailed)?;
+            self.env().emit_event(Withdrawn { owner, amount });
+            Ok(())
+        }
+
+        /// Call a meta transaction. The transaction calling this fn must contain a valid transaction inside it that is passed to this fn as `req`.
+        /// `req` will be verified by `verify` using the supplied `signature`. If the transaction is signed correctly, it will be executed.
+        /// The transferred value and tip are paid from the signer's deposit.
+        /// Returns what the call did. A transaction whose call failed is still executed and cannot be replayed,
+        /// any `Err` leaves the transaction untouched.
+        #[ink(message)]
+        pub fn execute(
+            &mut self,
+            req: Transaction,
+            signature: Signature,
+        ) -> Result<CallOutcome, Error> {
+            if self.config.paused {
+                return Err(Error::Paused)
+            }
+            self.ensure_migrated()?;
+
+            self.execute_transaction(req, signature)
+        }
+
+        /// Call several meta transactions in order, as `execute` would.
+        ///
+        /// Returns the outcome of every transaction, as `execute` would. In `AllOrNothing` mode the first failure,
+        /// including a failed call, fails the whole batch.
+        #[ink(message)]
+        pub fn execute_batch(
+            &mut self,
+            requests: Vec<(Transaction, Signature)>,
+            mode: BatchMode,
+        ) -> Result<Vec<Result<CallOutcome, Error>>, Error> {
+            if self.config.paused {
+                return Err(Error::Paused)
+            }
+            self.ensure_migrated()?;
+            self.check_relayer()?;
+
+            let mut results = Vec::with_capacity(requests.len());
+            for (index, (req, signature)) in requests.into_iter().enumerate() {
+                let result = self.execute_transaction(req, signature);
+                match result {
+                    // Never let a relayer burn transactions of the batch by sending too little gas
+                    Err(Error::InsufficientGas) => return Err(Error::InsufficientGas),
+                    // The call already succeeded and cannot be undone on its own
+                    Err(Error::FeePaymentFailed) => return Err(Error::FeePaymentFailed),
+                    Err(Error::TipPaymentFailed) => return Err(Error::TipPaymentFailed),
+                    Err(Error::PaymasterReimbursementFailed) => {
+                        return Err(Error::PaymasterReimbursementFailed)
+                    }
+                    _ => (),
+                }
+                if !matches!(result, Ok(CallOutcome::Succeeded(_))) {
+                    if let BatchMode::AllOrNothing = mode {
+                        return Err(Error::BatchItemFailed(index as u32))
+                    }
+                }
+                results.push(result);
+            }
+
+            Ok(results)
+        }
+
+        /// Verify and run a single meta transaction, paying its value and tip from the signer's deposit.
+        fn execute_transaction(
+            &mut self,
+            req: Transaction,
+            signature: Signature,
+        ) -> Result<CallOutcome, Error> {
+            self.is_executable(req.clone(), signature)?;
+
+            let caller = req.from;
+
+            // Assert that the signer deposited enough to fund the transaction
+            let cost = req
+                .transferred_value
+                .checked_add(req.tip)
+                .ok_or(Error::InsufficientDeposit)?;
+            let deposit = self.balance_of(caller);
+            if deposit < cost {
+                return Err(Error::InsufficientDeposit)
+            }
+
+            if let Some(paymaster) = req.paymaster {
+                self.request_sponsorship(paymaster, &req)?;
+            }
+
+            // Signature is valid, so use up the nonce and the deposit and then execute transaction.
+            // The deposit is charged before the call so the callee cannot withdraw it while re-entering.
+            self.use_nonce(&req);
+            self.deposit_data.deposits.insert(caller, &(deposit - cost));
+
+            // Assert that the callee gets all the gas it was signed with
+            let gas_before = self.env().gas_left();
+            check_gas_left(gas_before, req.gas_limit)?;
+
+            // Run the transaction
+            let result = build_call::<<Self as ::ink::env::ContractEnv>::Env>()
+                .call(req.callee)
+                .gas_limit(req.gas_limit)
+                .transferred_value(req.transferred_value)
+                .call_flags(CallFlags::default().set_allow_reentry(req.allow_reentry))
+                .exec_input(
+                    ExecutionInput::new(req.selector.into())
+                        .push_arg(CallInput(&call_input(&req, self.env().caller()))),
+                )
+                .returns::<CallOutput>()
+                .try_invoke();
+
+            let output = match call_result(result, req.returns_result) {
+                Ok(output) => output,
+                Err(error) => {
+                    // The call did not happen, so give back the deposit. The nonce stays used, which an `Err`
+                    // would revert.
+                    self.deposit_data.deposits.insert(caller, &deposit);
+                    return Ok(CallOutcome::Failed(error))
+                }
+            };
+            self.pay_fee(&req)?;
+            if req.tip > 0 {
+                self.env()
+                    .transfer(self.env().caller(), req.tip)
+                    .map_err(|_| Error::TipPaymentFailed)?;
+            }
+            if let Some(paymaster) = req.paymaster {
+                let gas_used = gas_before.saturating_sub(self.env().gas_left());
+                self.reimburse_relayer(paymaster, gas_used)?;
+            }
+            self.env().emit_event(Executed {
+                caller,
+                callee: req.callee,
+                encoded_transaction: req,
+            });
+            Ok(CallOutcome::Succeeded(output))
+        }
+
+        /// Check that the caller may relay transactions.
+        fn check_relayer(&self) -> Result<(), Error> {
+            if self.config.permissioned && !self.has_role(RELAYER, self.env().caller()) {
+                return Err(Error::RelayerNotAllowed)
+            }
+            Ok(())
+        }
+
+        /// Ask `paymaster` to sponsor `req`.
+        fn request_sponsorship(
+            &self,
+            paymaster: AccountId,
+            req: &Transaction,
+        ) -> Result<(), Error> {
+            let result = build_call::<<Self as ::ink::env::ContractEnv>::Env>()
+                .call(paymaster)
+                .gas_limit(0)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(PAYMASTER_VALIDATE_SELECTOR))
+                        .push_arg(req.from)
+                        .push_arg(req.callee)
+                        .push_arg(req.selector)
+                        .push_arg(req.gas_limit),
+                )
+                .returns::<Result<(), CallOutput>>()
+                .try_invoke();
+
+            match result {
+                Ok(Ok(Ok(()))) => Ok(()),
+                _ => Err(Error::PaymasterRejected),
+            }
+        }
+
+        /// Have `paymaster` pay the relayer for `gas_used`.
+        fn reimburse_relayer(&self, paymaster: AccountId, gas_used: u64) -> Result<(), Error> {
+            let result = build_call::<<Self as ::ink::env::ContractEnv>::Env>()
+                .call(paymaster)
+                .gas_limit(0)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(PAYMASTER_REIMBURSE_SELECTOR))
+                        .push_arg(self.env().caller())
+                        .push_arg(gas_used),
+                )
+                .returns::<Result<Balance, CallOutput>>()
+                .try_invoke();
+
+            match result {
+                Ok(Ok(Ok(_))) => Ok(()),
+                _ => Err(Error::PaymasterReimbursementFailed),
+            }
+        }
+
+        /// Have the fee collector transfer the fee of `req`, if any, from the signer to the fee recipient
+        /// or the relayer.
+        ///
+        /// The forwarder never spends allowances itself: any transaction could make it spend them
+        /// by calling `PSP22::transfer_from` on the token.
+        fn pay_fee(&self, req: &Transaction) -> Result<(), Error> {
+            let Some(fee) = &req.fee else {
+                return Ok(())
+            };
+            let fee_collector = self.get_fee_collector().ok_or(Error::FeePaymentFailed)?;
+            let recipient = fee.recipient.unwrap_or_else(|| self.env().caller());
+
+            let result = build_call::<<Self as ::ink::env::ContractEnv>::Env>()
+                .call(fee_collector)
+                .gas_limit(0)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(FEE_COLLECTOR_COLLECT_SELECTOR))
+                        .push_arg(fee.token)
+                        .push_arg(req.from)
+                        .push_arg(recipient)
+                        .push_arg(fee.amount),
+                )
+                .returns::<Result<(), CallOutput>>()
+                .try_invoke();
+
+            match result {
+                Ok(Ok(Ok(()))) => Ok(()),
+                _ => Err(Error::FeePaymentFailed),
+            }
+        }
+
+        /// Check that the nonce of `req` can be used.
+        fn check_nonce(&self, req: &Transaction) -> Result<(), Error> {
+            match req.nonce_mode {
+                NonceMode::Sequential => {
+                    // The last nonce cannot be used, there is no next nonce to move to
+                    if self.get_keyed_nonce(req.from, req.nonce_key) != req.nonce
+                        || req.nonce == Nonce::MAX
+                    {
+                        return Err(Error::IncorrectNonce)
+                    }
+                }
+                NonceMode::Unordered => {
+                    if self.is_nonce_used(req.from, req.nonce) {
+                        return Err(Error::NonceAlreadyUsed)
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        /// Mark the nonce of `req` as used. The nonce must have been checked with `check_nonce`.
+        fn use_nonce(&mut self, req: &Transaction) {
+            match req.nonce_mode {
+                NonceMode::Sequential => {
+                    self.nonce_data
+                        .nonces
+                        .insert((req.from, req.nonce_key), &(req.nonce + 1));
+                }
+                NonceMode::Unordered => {
+                    let (word_position, bit) = Self::bitmap_position(req.nonce);
+                    let word = self.nonce_bitmap(req.from, word_position) | bit;
+                    self.nonce_data
+                        .nonce_bitmaps
+                        .insert((req.from, word_position), &word);
+                }
+            }
+        }
+
+        /// Apply `cancellation` to the nonces or the epoch of `owner`.
+        /// Fails if the cancellation would have no effect.
+        fn apply_cancellation(
+            &mut self,
+            owner: AccountId,
+            cancellation: Cancellation,
+        ) -> Result<(), Error> {
+            match cancellation {
+                Cancellation::Nonce {
+                    nonce_mode: NonceMode::Sequential,
+                    nonce_key,
+                    nonce,
+                } => {
+                    if nonce < self.get_keyed_nonce(owner, nonce_key) {
+                        return Err(Error::IncorrectNonce)
+                    }
+                    let next = nonce.checked_add(1).ok_or(Error::IncorrectNonce)?;
+                    self.nonce_data.nonces.insert((owner, nonce_key), &next);
+                    self.env().emit_event(NonceCancelled {
+                        owner,
+                        nonce_mode: NonceMode::Sequential,
+                        nonce_key,
+                        nonce,
+                    });
+                }
+                Cancellation::Nonce {
+                    nonce_mode: NonceMode::Unordered,
+                    nonce_key,
+                    nonce,
+                } => {
+                    if self.is_nonce_used(owner, nonce) {
+                        return Err(Error::NonceAlreadyUsed)
+                    }
+                    let (word_position, bit) = Self::bitmap_position(nonce);
+                    let word = self.nonce_bitmap(owner, word_position) | bit;
+                    self.nonce_data
+                        .nonce_bitmaps
+                        .insert((owner, word_position), &word);
+                    self.env().emit_event(NonceCancelled {
+                        owner,
+                        nonce_mode: NonceMode::Unordered,
+                        nonce_key,
+                        nonce,
+                    });
+                }
+                Cancellation::NoncesUpTo { nonce_key, nonce } => {
+                    if nonce <= self.get_keyed_nonce(owner, nonce_key) {
+                        return Err(Error::IncorrectNonce)
+                    }
+                    self.nonce_data.nonces.insert((owner, nonce_key), &nonce);
+                    self.env().emit_event(NoncesInvalidated {
+                        owner,
+                        nonce_key,
+                        nonce,
+                    });
+                }
+                Cancellation::Epoch { epoch } => {
+                    if epoch != self.get_epoch(owner) {
+                        return Err(Error::IncorrectEpoch)
+                    }
+                    let epoch = epoch.checked_add(1).ok_or(Error::IncorrectEpoch)?;
+                    self.nonce_data.epochs.insert(owner, &epoch);
+                    self.env().emit_event(EpochIncremented { owner, epoch });
+                }
+            }
+            Ok(())
+        }
+
+        /// Split an unordered nonce into its bitmap word position and the bit within that word.
+        fn bitmap_position(nonce: Nonce) -> (u128, u128) {
+            (nonce >> 7, 1 << (nonce & 0x7f))
+        }
+
+        /// Check that `signature` over `req` was produced by `req.signer()`.
+        fn verify_signature<T: Signable>(
+            &self,
+            req: &T,
+            signature: &Signature,
+        ) -> Result<(), Error> {
+            let from = req.signer();
+
+            // Contracts cannot sign, so they are asked whether they approve the signature instead
+            if self.env().is_contract(&from) {
+                let Signature::Contract(signature) = signature else {
+                    return Err(Error::IncorrectSignature)
+                };
+                let message_hash = Self::blake2x256_hash(self.signing_payload(req));
+                if !self.is_valid_contract_signature(from, message_hash, signature) {
+                    return Err(Error::IncorrectSignature)
+                }
+                return Ok(())
+            }
+
+            let signer = match signature {
+                Signature::Ecdsa(signature) => {
+                    let message_hash = Self::blake2x256_hash(self.signing_payload(req));
+                    let pub_key = self
+                        .env()
+                        .ecdsa_recover(signature, &message_hash)
+                        .map_err(|_| Error::IncorrectSignature)?;
+                    Self::to_default_account_id(pub_key)
+                }
+                Signature::Eip712(signature) => {
+                    let pub_key = self
+                        .env()
+                        .ecdsa_recover(signature, &self.eip712_hash(req))
+                        .map_err(|_| Error::IncorrectSignature)?;
+                    let mut address = [0; 20];
+                    ink::env::ecdsa_to_eth_address(&pub_key, &mut address)
+                        .map_err(|_| Error::IncorrectSignature)?;
+                    Self::to_evm_account_id(address)
+                }
+                Signature::Sr25519(signature) => {
+                    let pub_key = schnorrkel::PublicKey::from_bytes(from.as_ref())
+                        .map_err(|_| Error::IncorrectSignature)?;
+                    let signature = schnorrkel::Signature::from_bytes(signature)
+                        .map_err(|_| Error::IncorrectSignature)?;
+                    let payload = self.signing_payload(req);
+                    pub_key
+                        .verify_simple(SR25519_SIGNING_CONTEXT, &payload, &signature)
+                        .or_else(|_| {
+                            pub_key.verify_simple(
+                                SR25519_SIGNING_CONTEXT,
+                                &wrap_bytes(&payload),
+                                &signature,
+                            )
+                        })
+                        .map_err(|_| Error::IncorrectSignature)?;
+                    from
+                }
+                Signature::Ed25519(signature) => {
+                    let pub_key = ed25519_dalek::PublicKey::from_bytes(from.as_ref())
+                        .map_err(|_| Error::IncorrectSignature)?;
+                    let signature = ed25519_dalek::Signature::from(*signature);
+                    let payload = self.signing_payload(req);
+                    pub_key
+                        .verify_strict(&payload, &signature)
+                        .or_else(|_| pub_key.verify_strict(&wrap_bytes(&payload), &signature))
+                        .map_err(|_| Error::IncorrectSignature)?;
+                    from
+                }
+                Signature::Contract(_) => return Err(Error::IncorrectSignature),
+            };
+
+            if signer != from {
+                return Err(Error::IncorrectSignature)
+            }
+
+            Ok(())
+        }
+
+        /// Ask the contract `signer` whether it approves `signature` over `hash`, with at most
+        /// `CONTRACT_SIGNATURE_GAS_LIMIT` gas. Running out of it counts as a refusal.
+        fn is_valid_contract_signature(
+            &self,
+            signer: AccountId,
+            hash: [u8; 32],
+            signature: &[u8],
+        ) -> bool {
+            let result = build_call::<<Self as ::ink::env::ContractEnv>::Env>()
+                .call(signer)
+                .gas_limit(CONTRACT_SIGNATURE_GAS_LIMIT)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(CONTRACT_SIGNER_IS_VALID_SIGNATURE_SELECTOR))
+                        .push_arg(hash)
+                        .push_arg(signature),
+                )
+                .returns::<bool>()
+                .try_invoke();
+
+            matches!(result, Ok(Ok(true)))
+        }
+
+        /// Compute the EIP-712 digest `keccak256("\x19\x01" ++ domainSeparator ++ hashStruct(req))`.
+        fn eip712_hash<T: Signable>(&self, req: &T) -> [u8; 32] {
+            let mut encoded = Vec::with_capacity(2 + 2 * 32);
+            encoded.extend_from_slice(b"\x19\x01");
+            encoded.extend_from_slice(&self.eip712_domain_separator());
+            encoded.extend_from_slice(&req.eip712_struct_hash());
+            Self::keccak256_hash(&encoded)
+        }
+
+        /// Build the bytes a signer signs for `req`: the domain separator followed by the SCALE encoded message.
+        fn signing_payload<T: Encode>(&self, req: &T) -> Vec<u8> {
+            let mut payload = self.domain_separator().to_vec();
+            req.encode_to(&mut payload);
+            payload
+        }
+
+        /// Convert a compressed 33 byte ECDSA public key into a 32 byte Substrate address
+        fn to_default_account_id(compressed_pub_key: [u8; 33]) -> ink::primitives::AccountId {
+            use ink::env::hash;
+
+            let mut output = <hash::Blake2x256 as hash::HashOutput>::Type::default();
+            ink::env::hash_bytes::<hash::Blake2x256>(&compressed_pub_key[..], &mut output);
+
+            output.into()
+        }
+
+        /// Map a 20 byte Ethereum address to a 32 byte Substrate address, as Astar does for EVM accounts.
+        fn to_evm_account_id(address: [u8; 20]) -> AccountId {
+            let mut preimage = b"evm:".to_vec();
+            preimage.extend_from_slice(&address);
+            Self::blake2x256_hash(preimage).into()
+        }
+
+        /// Compute the 32 byte Blake 256 hash of the supplied byte vector.
+        fn blake2x256_hash(bytes: Vec<u8>) -> [u8; 32] {
+            use ink::env::hash;
+
+            let mut output = <hash::Blake2x256 as hash::HashOutput>::Type::default();
+            ink::env::hash_bytes::<hash::Blake2x256>(&bytes[..], &mut output);
+
+            output
+        }
+
+        /// Compute the 32 byte Keccak 256 hash of the supplied bytes.
+        fn keccak256_hash(bytes: &[u8]) -> [u8; 32] {
+            use ink::env::hash;
+
+            let mut output = <hash::Keccak256 as hash::HashOutput>::Type::default();
+            ink::env::hash_bytes::<hash::Keccak256>(bytes, &mut output);
+
+            output
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use secp256k1::{
+            Message,
+            SecretKey,
+            SECP256K1,
+        };
+
+        const GENESIS_HASH: [u8; 32] = [0x42; 32];
+        const EVM_CHAIN_ID: u64 = 592;
+
+        fn signer() -> (SecretKey, AccountId) {
+            let secret_key = SecretKey::from_slice(&[0x11; 32]).unwrap();
+            let public_key = secret_key.public_key(SECP256K1).serialize();
+            (secret_key, Forwarder::to_default_account_id(public_key))
+        }
+
+        fn transaction(from: AccountId) -> Transaction {
+            Transaction {
+                from,
+                callee: AccountId::from([0x2; 32]),
+                selector: [0x63, 0x3a, 0xa5, 0x51],
+                input: Vec::new(),
+                sender_mode: SenderMode::Argument,
+                returns_result: false,
+                transferred_value: 0,
+                tip: 0,
+                gas_limit: 1_000_000_000,
+                allow_reentry: false,
+                nonce_mode: NonceMode::Sequential,
+                nonce_key: 0,
+                nonce: 0,
+                epoch: 0,
+                validity_unit: ValidityUnit::Timestamp,
+                valid_after: 0,
+                valid_until: u64::MAX,
+                fee: None,
+                paymaster: None,
+                relayer: None,
+            }
+        }
+
+        fn evm_signer() -> (SecretKey, AccountId) {
+            let secret_key = SecretKey::from_slice(&[0x22; 32]).unwrap();
+            let public_key = secret_key.public_key(SECP256K1).serialize();
+            let mut address = [0; 20];
+            ink::env::ecdsa_to_eth_address(&public_key, &mut address).unwrap();
+            (secret_key, Forwarder::to_evm_account_id(address))
+        }
+
+        fn sign<T: Signable>(forwarder: &Forwarder, req: &T, secret_key: &SecretKey) -> Signature {
+            let message_hash = Forwarder::blake2x256_hash(forwarder.signing_payload(req));
+            Signature::Ecdsa(sign_hash(message_hash, secret_key))
+        }
+
+        fn sign_eip712<T: Signable>(
+            forwarder: &Forwarder,
+            req: &T,
+            secret_key: &SecretKey,
+        ) -> Signature {
+            let mut signature = sign_hash(forwarder.eip712_hash(req), secret_key);
+            // Ethereum wallets produce `v` as 27 or 28.
+            signature[64] += 27;
+            Signature::Eip712(signature)
+        }
+
+        fn sign_sr25519(
+            forwarder: &Forwarder,
+            req: &Transaction,
+            keypair: &schnorrkel::Keypair,
+        ) -> Signature {
+            let payload = forwarder.signing_payload(req);
+            Signature::Sr25519(
+                keypair
+                    .sign_simple(SR25519_SIGNING_CONTEXT, &payload)
+                    .to_bytes(),
+            )
+        }
+
+        fn sign_ed25519(
+            forwarder: &Forwarder,
+            req: &Transaction,
+            keypair: &ed25519_dalek::Keypair,
+        ) -> Signature {
+            use ed25519_dalek::Signer;
+
+            let payload = forwarder.signing_payload(req);
+            Signature::Ed25519(keypair.sign(&payload).to_bytes())
+        }
+
+        fn sr25519_signer() -> (schnorrkel::Keypair, AccountId) {
+            let keypair = schnorrkel::MiniSecretKey::from_bytes(&[0x33; 32])
+                .unwrap()
+                .expand_to_keypair(schnorrkel::ExpansionMode::Ed25519);
+            let from = AccountId::from(keypair.public.to_bytes());
+            (keypair, from)
+        }
+
+        fn ed25519_signer() -> (ed25519_dalek::Keypair, AccountId) {
+            let secret = ed25519_dalek::SecretKey::from_bytes(&[0x44; 32]).unwrap();
+            let public = ed25519_dalek::PublicKey::from(&secret);
+            let from = AccountId::from(public.to_bytes());
+            (ed25519_dalek::Keypair { secret, public }, from)
+        }
+
+        fn sign_hash(message_hash: [u8; 32], secret_key: &SecretKey) -> [u8; 65] {
+            let message = Message::from_slice(&message_hash).unwrap();
+            let (recovery_id, compact) = SECP256K1
+                .sign_ecdsa_recoverable(&message, secret_key)
+                .serialize_compact();
+            let mut signature = [0; 65];
+            signature[..64].copy_from_slice(&compact);
+            signature[64] = recovery_id.to_i32() as u8;
+            signature
+        }
+
+        #[ink::test]
+        fn domain_separator_works() {
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let expected = Forwarder::blake2x256_hash(
+                (
+                    DOMAIN_NAME,
+                    DOMAIN_VERSION,
+                    Hash::from(GENESIS_HASH),
+                    ink::env::test::callee::<Environment>(),
+                )
+                    .encode(),
+            );
+            assert_eq!(forwarder.domain_separator(), expected);
+        }
+
+        #[ink::test]
+        fn verify_works() {
+            let (secret_key, from) = signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let req = transaction(from);
+            let signature = sign(&forwarder, &req, &secret_key);
+            assert_eq!(forwarder.verfiy(req, signature), Ok(()));
+        }
+
+        #[ink::test]
+        fn verify_signature_from_other_chain_fails() {
+            let (secret_key, from) = signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let other_chain_forwarder = Forwarder::new([0x43; 32].into(), EVM_CHAIN_ID);
+            let req = transaction(from);
+            let signature = sign(&other_chain_forwarder, &req, &secret_key);
+            assert_eq!(
+                forwarder.verfiy(req, signature),
+                Err(Error::IncorrectSignature)
+            );
+        }
+
+        #[ink::test]
+        fn contract_signature_of_non_contract_fails() {
+            let (_, from) = signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let req = transaction(from);
+            assert_eq!(
+                forwarder.verfiy(req, Signature::Contract(vec![0x1; 65])),
+                Err(Error::IncorrectSignature)
+            );
+        }
+
+        #[ink::test]
+        fn contract_signer_selector_matches_multisig() {
+            // The selector `Multisig` registers for its `ContractSigner` implementation
+            assert_eq!(
+                CONTRACT_SIGNER_IS_VALID_SIGNATURE_SELECTOR,
+                ink::selector_bytes!("ContractSigner::is_valid_signature")
+            );
+        }
+
+        #[ink::test]
+        fn key_signature_of_contract_fails() {
+            let (secret_key, from) = signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let req = transaction(from);
+            let signature = sign(&forwarder, &req, &secret_key);
+            assert_eq!(forwarder.verfiy(req.clone(), signature.clone()), Ok(()));
+
+            // Once `from` is a contract, only the contract itself can approve its transactions
+            ink::env::test::set_contract::<Environment>(from);
+            assert_eq!(
+                forwarder.verfiy(req, signature),
+                Err(Error::IncorrectSignature)
+            );
+        }
+
+        #[ink::test]
+        fn verify_signature_for_other_forwarder_fails() {
+            let (secret_key, from) = signer();
+            let req = transaction(from);
+
+            ink::env::test::set_callee::<Environment>(AccountId::from([0x3; 32]));
+            let other_forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let signature = sign(&other_forwarder, &req, &secret_key);
+
+            ink::env::test::set_callee::<Environment>(AccountId::from([0x4; 32]));
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            assert_eq!(
+                forwarder.verfiy(req, signature),
+                Err(Error::IncorrectSignature)
+            );
+        }
+
+        #[ink::test]
+        fn verify_incorrect_nonce_fails() {
+            let (secret_key, from) = signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let mut req = transaction(from);
+            req.nonce = 1;
+            let signature = sign(&forwarder, &req, &secret_key);
+            assert_eq!(forwarder.verfiy(req, signature), Err(Error::IncorrectNonce));
+        }
+
+        #[ink::test]
+        fn eip712_hash_matches_known_answer() {
+            // Computed independently of this contract from the EIP-712 specification (`encodeType`,
+            // `encodeData` and `hashStruct`), with an implementation checked against the example of the
+            // specification. Any change to the encoding breaks signatures made by wallets.
+            const DOMAIN_SEPARATOR: [u8; 32] = [
+                0x9e, 0x63, 0x4f, 0x2c, 0xc3, 0xf3, 0xab, 0x96, 0xc8, 0xbd, 0x66, 0x91, 0xc0, 0x3f,
+                0x87, 0xe0, 0xb9, 0x77, 0x87, 0x00, 0x6c, 0x2d, 0x39, 0xca, 0x21, 0xbc, 0x8b, 0x6c,
+                0x55, 0xca, 0xa1, 0x73,
+            ];
+            const DIGEST: [u8; 32] = [
+                0x86, 0xca, 0xb0, 0x29, 0xef, 0xef, 0x87, 0x06, 0x60, 0xb1, 0xd0, 0x68, 0x7d, 0x51,
+                0x92, 0xa2, 0xad, 0xa2, 0xff, 0xa4, 0x78, 0x58, 0x31, 0x38, 0x3a, 0x62, 0xee, 0x0b,
+                0x9e, 0x96, 0x54, 0xdc,
+            ];
+
+            ink::env::test::set_callee::<Environment>(AccountId::from([0xf6; 32]));
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let req = Transaction {
+                from: AccountId::from([0xa1; 32]),
+                callee: AccountId::from([0xb2; 32]),
+                selector: [0x63, 0x3a, 0xa5, 0x51],
+                input: vec![0xde, 0xad, 0xbe, 0xef],
+                sender_mode: SenderMode::Trailing,
+                returns_result: true,
+                transferred_value: 1_000,
+                tip: 7,
+                gas_limit: 5_000_000_000,
+                allow_reentry: false,
+                nonce_mode: NonceMode::Unordered,
+                nonce_key: 3,
+                nonce: 258,
+                epoch: 2,
+                validity_unit: ValidityUnit::BlockNumber,
+                valid_after: 100,
+                valid_until: 2_000,
+                fee: Some(Fee {
+                    token: AccountId::from([0xc3; 32]),
+                    amount: 12_345,
+                    recipient: None,
+                }),
+                paymaster: Some(AccountId::from([0xd4; 32])),
+                relayer: Some(AccountId::from([0xe5; 32])),
+            };
+
+            assert_eq!(forwarder.eip712_domain_separator(), DOMAIN_SEPARATOR);
+            assert_eq!(forwarder.eip712_hash(&req), DIGEST);
+        }
+
+        #[ink::test]
+        fn verify_eip712_works() {
+            let (secret_key, from) = evm_signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let req = transaction(from);
+            let signature = sign_eip712(&forwarder, &req, &secret_key);
+            assert_eq!(forwarder.verfiy(req, signature), Ok(()));
+        }
+
+        #[ink::test]
+        fn verify_eip712_signature_as_ecdsa_fails() {
+            let (secret_key, from) = evm_signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let req = transaction(from);
+            let Signature::Eip712(signature) = sign_eip712(&forwarder, &req, &secret_key) else {
+                unreachable!()
+            };
+            assert_eq!(
+                forwarder.verfiy(req, Signature::Ecdsa(signature)),
+                Err(Error::IncorrectSignature)
+            );
+        }
+
+        #[ink::test]
+        fn verify_eip712_signature_from_other_evm_chain_fails() {
+            let (secret_key, from) = evm_signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let other_chain_forwarder = Forwarder::new(GENESIS_HASH.into(), 1);
+            let req = transaction(from);
+            let signature = sign_eip712(&other_chain_forwarder, &req, &secret_key);
+            assert_eq!(
+                forwarder.verfiy(req, signature),
+                Err(Error::IncorrectSignature)
+            );
+        }
+
+        #[ink::test]
+        fn eip712_uint_is_big_endian() {
+            let mut expected = [0; 32];
+            expected[30] = 0x01;
+            expected[31] = 0x02;
+            assert_eq!(eip712_uint(0x0102), expected);
+        }
+
+        #[ink::test]
+        fn verify_sr25519_works() {
+            let (keypair, from) = sr25519_signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let req = transaction(from);
+            let signature = sign_sr25519(&forwarder, &req, &keypair);
+            assert_eq!(forwarder.verfiy(req, signature), Ok(()));
+        }
+
+        #[ink::test]
+        fn verify_wrapped_sr25519_works() {
+            let (keypair, from) = sr25519_signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let req = transaction(from);
+            // What the polkadot.js extension signs for `signRaw`
+            let payload = wrap_bytes(&forwarder.signing_payload(&req));
+            let signature = keypair.sign_simple(SR25519_SIGNING_CONTEXT, &payload);
+            assert_eq!(
+                forwarder.verfiy(req, Signature::Sr25519(signature.to_bytes())),
+                Ok(())
+            );
+        }
+
+        #[ink::test]
+        fn verify_sr25519_signature_for_other_account_fails() {
+            let (keypair, _) = sr25519_signer();
+            let (_, from) = ed25519_signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let req = transaction(from);
+            let signature = sign_sr25519(&forwarder, &req, &keypair);
+            assert_eq!(
+                forwarder.verfiy(req, signature),
+                Err(Error::IncorrectSignature)
+            );
+        }
+
+        #[ink::test]
+        fn verify_ed25519_works() {
+            let (keypair, from) = ed25519_signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let req = transaction(from);
+            let signature = sign_ed25519(&forwarder, &req, &keypair);
+            assert_eq!(forwarder.verfiy(req, signature), Ok(()));
+        }
+
+        #[ink::test]
+        fn verify_wrapped_ed25519_works() {
+            use ed25519_dalek::Signer;
+
+            let (keypair, from) = ed25519_signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let req = transaction(from);
+            let payload = wrap_bytes(&forwarder.signing_payload(&req));
+            let signature = keypair.sign(&payload);
+            assert_eq!(
+                forwarder.verfiy(req, Signature::Ed25519(signature.to_bytes())),
+                Ok(())
+            );
+        }
+
+        #[ink::test]
+        fn verify_ed25519_tampered_transaction_fails() {
+            let (keypair, from) = ed25519_signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let mut req = transaction(from);
+            let signature = sign_ed25519(&forwarder, &req, &keypair);
+            req.input = vec![0x1];
+            assert_eq!(
+                forwarder.verfiy(req, signature),
+                Err(Error::IncorrectSignature)
+            );
+        }
+
+        #[ink::test]
+        fn deposit_and_withdraw_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            ink::env::test::set_account_balance::<Environment>(accounts.bob, 0);
+            ink::env::test::set_account_balance::<Environment>(
+                ink::env::test::callee::<Environment>(),
+                10,
+            );
+
+            ink::env::test::set_value_transferred::<Environment>(10);
+            forwarder.deposit();
+            assert_eq!(forwarder.balance_of(accounts.bob), 10);
+            assert_eq!(forwarder.balance_of(accounts.alice), 0);
+
+            assert_eq!(forwarder.withdraw(11), Err(Error::InsufficientDeposit));
+            assert_eq!(forwarder.withdraw(4), Ok(()));
+            assert_eq!(forwarder.balance_of(accounts.bob), 6);
+            assert_eq!(
+                ink::env::test::get_account_balance::<Environment>(accounts.bob),
+                Ok(4)
+            );
+        }
+
+        #[ink::test]
+        fn execute_without_deposit_fails() {
+            let (secret_key, from) = signer();
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let mut req = transaction(from);
+            req.transferred_value = 10;
+            req.tip = 1;
+            let signature = sign(&forwarder, &req, &secret_key);
+
+            ink::env::test::set_caller::<Environment>(from);
+            ink::env::test::set_value_transferred::<Environment>(10);
+            forwarder.deposit();
+            assert_eq!(
+                forwarder.execute(req.clone(), signature),
+                Err(Error::InsufficientDeposit)
+            );
+            assert_eq!(forwarder.get_nonce(from), 0);
+        }
+
+        #[ink::test]
+        fn verify_tampered_tip_fails() {
+            let (secret_key, from) = signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let mut req = transaction(from);
+            req.tip = 1;
+            let signature = sign(&forwarder, &req, &secret_key);
+
+            req.tip = 2;
+            assert_eq!(
+                forwarder.verfiy(req, signature),
+                Err(Error::IncorrectSignature)
+            );
+        }
+
+        #[ink::test]
+        fn verify_tampered_returns_result_fails() {
+            let (secret_key, from) = signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let mut req = transaction(from);
+            req.returns_result = true;
+            let signature = sign(&forwarder, &req, &secret_key);
+
+            // Otherwise a relayer could have a reverted call count as a success
+            req.returns_result = false;
+            assert_eq!(
+                forwarder.verfiy(req, signature),
+                Err(Error::IncorrectSignature)
+            );
+        }
+
+        #[ink::test]
+        fn execute_batch_all_or_nothing_reports_failed_index() {
+            let (secret_key, from) = signer();
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let mut req = transaction(from);
+            req.nonce = 1;
+            let signature = sign(&forwarder, &req, &secret_key);
+
+            assert_eq!(
+                forwarder.execute_batch(vec![(req, signature)], BatchMode::AllOrNothing),
+                Err(Error::BatchItemFailed(0))
+            );
+        }
+
+        #[ink::test]
+        fn execute_batch_best_effort_keeps_failed_value_deposited() {
+            let (secret_key, from) = signer();
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+
+            let mut expired = transaction(from);
+            expired.transferred_value = 7;
+            expired.valid_until = 0;
+            let expired_signature = sign(&forwarder, &expired, &secret_key);
+            let mut wrong_nonce = transaction(from);
+            wrong_nonce.transferred_value = 3;
+            wrong_nonce.nonce = 5;
+            let wrong_nonce_signature = sign(&forwarder, &wrong_nonce, &secret_key);
+
+            ink::env::test::set_caller::<Environment>(from);
+            ink::env::test::set_value_transferred::<Environment>(10);
+            forwarder.deposit();
+
+            assert_eq!(
+                forwarder.execute_batch(
+                    vec![
+                        (expired, expired_signature),
+                        (wrong_nonce, wrong_nonce_signature)
+                    ],
+                    BatchMode::BestEffort
+                ),
+                Ok(vec![
+                    Err(Error::TransactionExpired),
+                    Err(Error::IncorrectNonce)
+                ])
+            );
+            assert_eq!(forwarder.balance_of(from), 10);
+        }
+
+        #[ink::test]
+        fn keyed_nonces_are_independent() {
+            let (secret_key, from) = signer();
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            forwarder.nonce_data.nonces.insert((from, 0), &3);
+
+            assert_eq!(forwarder.get_nonce(from), 3);
+            assert_eq!(forwarder.get_keyed_nonce(from, 0), 3);
+            assert_eq!(forwarder.get_keyed_nonce(from, 1), 0);
+
+            let req = transaction(from);
+            let signature = sign(&forwarder, &req, &secret_key);
+            assert_eq!(forwarder.verfiy(req, signature), Err(Error::IncorrectNonce));
+
+            let mut req = transaction(from);
+            req.nonce_key = 1;
+            let signature = sign(&forwarder, &req, &secret_key);
+            assert_eq!(forwarder.verfiy(req, signature), Ok(()));
+        }
+
+        #[ink::test]
+        fn unordered_nonce_works() {
+            let (secret_key, from) = signer();
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let mut req = transaction(from);
+            req.nonce_mode = NonceMode::Unordered;
+            req.nonce = 130;
+            let signature = sign(&forwarder, &req, &secret_key);
+            assert_eq!(forwarder.verfiy(req.clone(), signature.clone()), Ok(()));
+
+            forwarder.use_nonce(&req);
+            assert!(forwarder.is_nonce_used(from, 130));
+            assert!(!forwarder.is_nonce_used(from, 2));
+            assert_eq!(forwarder.nonce_bitmap(from, 1), 1 << 2);
+            assert_eq!(forwarder.get_nonce(from), 0);
+            assert_eq!(
+                forwarder.verfiy(req, signature),
+                Err(Error::NonceAlreadyUsed)
+            );
+        }
+
+        #[ink::test]
+        fn invalidate_unordered_nonces_works() {
+            let (secret_key, from) = signer();
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+
+            ink::env::test::set_caller::<Environment>(from);
+            forwarder.invalidate_unordered_nonces(0, 0b101);
+            assert!(forwarder.is_nonce_used(from, 0));
+            assert!(!forwarder.is_nonce_used(from, 1));
+            assert!(forwarder.is_nonce_used(from, 2));
+            assert_eq!(ink::env::test::recorded_events().count(), 1);
+
+            let mut req = transaction(from);
+            req.nonce_mode = NonceMode::Unordered;
+            req.nonce = 2;
+            let signature = sign(&forwarder, &req, &secret_key);
+            assert_eq!(
+                forwarder.verfiy(req, signature),
+                Err(Error::NonceAlreadyUsed)
+            );
+        }
+
+        #[ink::test]
+        fn verify_transaction_from_past_epoch_fails() {
+            let (secret_key, from) = signer();
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let req = transaction(from);
+            let signature = sign(&forwarder, &req, &secret_key);
+
+            ink::env::test::set_caller::<Environment>(from);
+            assert_eq!(forwarder.increment_epoch(), Ok(1));
+            assert_eq!(forwarder.get_epoch(from), 1);
+            assert_eq!(
+                forwarder.verfiy(req.clone(), signature),
+                Err(Error::IncorrectEpoch)
+            );
+
+            let mut req = req;
+            req.epoch = 1;
+            let signature = sign(&forwarder, &req, &secret_key);
+            assert_eq!(forwarder.verfiy(req, signature), Ok(()));
+        }
+
+        #[ink::test]
+        fn cancel_sequential_nonce_works() {
+            let (_, from) = signer();
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+
+            ink::env::test::set_caller::<Environment>(from);
+            let cancellation = Cancellation::Nonce {
+                nonce_mode: NonceMode::Sequential,
+                nonce_key: 0,
+                nonce: 2,
+            };
+            assert_eq!(forwarder.cancel(cancellation), Ok(()));
+            assert_eq!(forwarder.get_nonce(from), 3);
+            assert_eq!(forwarder.cancel(cancellation), Err(Error::IncorrectNonce));
+            assert_eq!(ink::env::test::recorded_events().count(), 1);
+        }
+
+        #[ink::test]
+        fn last_sequential_nonce_cannot_be_used() {
+            let (secret_key, from) = signer();
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+
+            ink::env::test::set_caller::<Environment>(from);
+            assert_eq!(forwarder.invalidate_nonces_up_to(0, Nonce::MAX), Ok(()));
+            assert_eq!(forwarder.get_nonce(from), Nonce::MAX);
+
+            let mut req = transaction(from);
+            req.nonce = Nonce::MAX;
+            let signature = sign(&forwarder, &req, &secret_key);
+            assert_eq!(
+                forwarder.verfiy(req.clone(), signature.clone()),
+                Err(Error::IncorrectNonce)
+            );
+            assert_eq!(
+                forwarder.execute(req, signature),
+                Err(Error::IncorrectNonce)
+            );
+        }
+
+        #[ink::test]
+        fn cancel_rejects_the_last_sequential_nonce() {
+            let (_, from) = signer();
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+
+            ink::env::test::set_caller::<Environment>(from);
+            assert_eq!(
+                forwarder.cancel(Cancellation::Nonce {
+                    nonce_mode: NonceMode::Sequential,
+                    nonce_key: 0,
+                    nonce: Nonce::MAX,
+                }),
+                Err(Error::IncorrectNonce)
+            );
+            assert_eq!(forwarder.get_nonce(from), 0);
+        }
+
+        #[ink::test]
+        fn invalidate_nonces_up_to_works() {
+            let (_, from) = signer();
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+
+            ink::env::test::set_caller::<Environment>(from);
+            assert_eq!(forwarder.invalidate_nonces_up_to(1, 10), Ok(()));
+            assert_eq!(forwarder.get_keyed_nonce(from, 1), 10);
+            assert_eq!(forwarder.get_nonce(from), 0);
+            assert_eq!(
+                forwarder.invalidate_nonces_up_to(1, 10),
+                Err(Error::IncorrectNonce)
+            );
+        }
+
+        #[ink::test]
+        fn cancel_by_signature_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let (secret_key, from) = signer();
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let req = CancelRequest {
+                from,
+                cancellation: Cancellation::Nonce {
+                    nonce_mode: NonceMode::Unordered,
+                    nonce_key: 0,
+                    nonce: 7,
+                },
+            };
+            let signature = sign(&forwarder, &req, &secret_key);
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                forwarder.cancel_by_signature(req.clone(), signature.clone()),
+                Ok(())
+            );
+            assert!(forwarder.is_nonce_used(from, 7));
+            assert_eq!(
+                forwarder.cancel_by_signature(req, signature),
+                Err(Error::NonceAlreadyUsed)
+            );
+        }
+
+        #[ink::test]
+        fn cancel_by_signature_of_other_account_fails() {
+            let (secret_key, _) = signer();
+            let (_, other) = evm_signer();
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let req = CancelRequest {
+                from: other,
+                cancellation: Cancellation::Epoch { epoch: 0 },
+            };
+            let signature = sign(&forwarder, &req, &secret_key);
+            assert_eq!(
+                forwarder.cancel_by_signature(req, signature),
+                Err(Error::IncorrectSignature)
+            );
+            assert_eq!(forwarder.get_epoch(other), 0);
+        }
+
+        #[ink::test]
+        fn cancel_epoch_by_eip712_signature_works() {
+            let (secret_key, from) = evm_signer();
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let req = CancelRequest {
+                from,
+                cancellation: Cancellation::Epoch { epoch: 0 },
+            };
+            let signature = sign_eip712(&forwarder, &req, &secret_key);
+            assert_eq!(
+                forwarder.cancel_by_signature(req.clone(), signature.clone()),
+                Ok(())
+            );
+            assert_eq!(forwarder.get_epoch(from), 1);
+            // The signed epoch cancellation cannot be replayed to move on again.
+            assert_eq!(
+                forwarder.cancel_by_signature(req, signature),
+                Err(Error::IncorrectEpoch)
+            );
+        }
+
+        #[ink::test]
+        fn call_result_works() {
+            assert_eq!(call_result(Ok(Ok(CallOutput(vec![]))), true), Ok(vec![]));
+            assert_eq!(
+                call_result(Ok(Ok(CallOutput(vec![0x00, 0x2a]))), true),
+                Ok(vec![0x00, 0x2a])
+            );
+            assert_eq!(
+                call_result(Ok(Ok(CallOutput(vec![0x01, 0x02]))), true),
+                Err(Error::CalleeReverted(vec![0x02]))
+            );
+            assert_eq!(
+                call_result(Ok(Err(ink::LangError::CouldNotReadInput)), true),
+                Err(Error::CalleeDispatchFailed(
+                    ink::LangError::CouldNotReadInput
+                ))
+            );
+            assert_eq!(
+                call_result(Err(ink::env::Error::CalleeTrapped), true),
+                Err(Error::TransactionFailed)
+            );
+        }
+
+        #[ink::test]
+        fn call_result_of_other_return_types_is_not_a_revert() {
+            // `true` and `Some(..)` start with `0x01` like an `Err`
+            assert_eq!(
+                call_result(Ok(Ok(CallOutput(vec![0x01]))), false),
+                Ok(vec![0x01])
+            );
+            assert_eq!(
+                call_result(Ok(Ok(CallOutput(vec![0x01, 0x2a]))), false),
+                Ok(vec![0x01, 0x2a])
+            );
+            assert_eq!(
+                call_result(Ok(Err(ink::LangError::CouldNotReadInput)), false),
+                Err(Error::CalleeDispatchFailed(
+                    ink::LangError::CouldNotReadInput
+                ))
+            );
+        }
+
+        #[ink::test]
+        fn call_output_decodes_all_bytes() {
+            let output: ink::MessageResult<CallOutput> =
+                scale::Decode::decode(&mut &[0x00, 0x01, 0x02][..]).unwrap();
+            assert!(matches!(output, Ok(CallOutput(bytes)) if bytes == vec![0x01, 0x02]));
+        }
+
+        #[ink::test]
+        fn check_gas_left_works() {
+            assert_eq!(check_gas_left(GAS_SAFETY_MARGIN + 100, 100), Ok(()));
+            assert_eq!(
+                check_gas_left(GAS_SAFETY_MARGIN + 99, 100),
+                Err(Error::InsufficientGas)
+            );
+            assert_eq!(
+                check_gas_left(u64::MAX, u64::MAX),
+                Err(Error::InsufficientGas)
+            );
+        }
+
+        #[ink::test]
+        fn zero_gas_limit_is_rejected() {
+            let (secret_key, from) = signer();
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let mut req = transaction(from);
+            req.gas_limit = 0;
+            let signature = sign(&forwarder, &req, &secret_key);
+
+            assert_eq!(
+                forwarder.is_executable(req.clone(), signature.clone()),
+                Err(Error::ZeroGasLimit)
+            );
+            assert_eq!(
+                forwarder.execute_batch(vec![(req, signature)], BatchMode::BestEffort),
+                Ok(vec![Err(Error::ZeroGasLimit)])
+            );
+            assert_eq!(forwarder.get_nonce(from), 0);
+        }
+
+        #[ink::test]
+        fn call_input_appends_relayer_and_signer() {
+            let from = AccountId::from([0x7; 32]);
+            let relayer = AccountId::from([0x8; 32]);
+            let mut req = transaction(from);
+            req.input = vec![0x1, 0x2];
+
+            // A SCALE `Vec<u8>` of 32 bytes is prefixed with its compact length
+            let mut expected = vec![0x1, 0x2, 32 << 2];
+            expected.extend_from_slice(from.as_ref());
+            expected.extend_from_slice(relayer.as_ref());
+            expected.extend_from_slice(from.as_ref());
+            assert_eq!(call_input(&req, relayer), expected);
+
+            req.sender_mode = SenderMode::Trailing;
+            let mut expected = vec![0x1, 0x2];
+            expected.extend_from_slice(relayer.as_ref());
+            expected.extend_from_slice(from.as_ref());
+            assert_eq!(call_input(&req, relayer), expected);
+        }
+
+        #[ink::test]
+        fn is_executable_checks_timestamp_window() {
+            let (secret_key, from) = signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let mut req = transaction(from);
+            req.valid_after = 1_000;
+            req.valid_until = 2_000;
+            let signature = sign(&forwarder, &req, &secret_key);
+
+            ink::env::test::set_block_timestamp::<Environment>(999);
+            assert_eq!(
+                forwarder.is_executable(req.clone(), signature.clone()),
+                Err(Error::TransactionNotYetValid)
+            );
+            ink::env::test::set_block_timestamp::<Environment>(1_000);
+            assert_eq!(
+                forwarder.is_executable(req.clone(), signature.clone()),
+                Ok(())
+            );
+            ink::env::test::set_block_timestamp::<Environment>(2_000);
+            assert_eq!(
+                forwarder.is_executable(req, signature),
+                Err(Error::TransactionExpired)
+            );
+        }
+
+        #[ink::test]
+        fn is_executable_checks_block_number_window() {
+            let (secret_key, from) = signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let mut req = transaction(from);
+            req.validity_unit = ValidityUnit::BlockNumber;
+            req.valid_after = 1;
+            req.valid_until = 2;
+            let signature = sign(&forwarder, &req, &secret_key);
+
+            ink::env::test::set_block_timestamp::<Environment>(1_500);
+            assert_eq!(
+                forwarder.is_executable(req.clone(), signature.clone()),
+                Err(Error::TransactionNotYetValid)
+            );
+            ink::env::test::advance_block::<Environment>();
+            assert_eq!(
+                forwarder.is_executable(req.clone(), signature.clone()),
+                Ok(())
+            );
+            ink::env::test::advance_block::<Environment>();
+            assert_eq!(
+                forwarder.is_executable(req, signature),
+                Err(Error::TransactionExpired)
+            );
+        }
+
+        #[ink::test]
+        fn is_executable_checks_signature() {
+            let (secret_key, from) = signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let req = transaction(from);
+            let signature = sign(&forwarder, &req, &secret_key);
+            let mut other = req;
+            other.gas_limit += 1;
+            assert_eq!(
+                forwarder.is_executable(other, signature),
+                Err(Error::IncorrectSignature)
+            );
+        }
+
+        #[ink::test]
+        fn verify_tampered_fee_fails() {
+            let (secret_key, from) = signer();
+            let (evm_secret_key, evm_from) = evm_signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let fee = Fee {
+                token: AccountId::from([0x5; 32]),
+                amount: 100,
+                recipient: None,
+            };
+            let tampered_fee = Fee {
+                amount: 1_000,
+                ..fee.clone()
+            };
+
+            let mut req = transaction(from);
+            req.fee = Some(fee.clone());
+            let signature = sign(&forwarder, &req, &secret_key);
+            assert_eq!(forwarder.verfiy(req.clone(), signature.clone()), Ok(()));
+            req.fee = Some(tampered_fee.clone());
+            assert_eq!(
+                forwarder.verfiy(req, signature),
+                Err(Error::IncorrectSignature)
+            );
+
+            let mut req = transaction(evm_from);
+            req.fee = Some(fee);
+            let signature = sign_eip712(&forwarder, &req, &evm_secret_key);
+            assert_eq!(forwarder.verfiy(req.clone(), signature.clone()), Ok(()));
+            req.fee = Some(tampered_fee);
+            assert_eq!(
+                forwarder.verfiy(req, signature),
+                Err(Error::IncorrectSignature)
+            );
+        }
+
+        #[ink::test]
+        fn verify_tampered_paymaster_fails() {
+            let (secret_key, from) = signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let mut req = transaction(from);
+            req.paymaster = Some(AccountId::from([0x6; 32]));
+            let signature = sign(&forwarder, &req, &secret_key);
+            assert_eq!(forwarder.verfiy(req.clone(), signature.clone()), Ok(()));
+
+            req.paymaster = None;
+            assert_eq!(
+                forwarder.verfiy(req, signature),
+                Err(Error::IncorrectSignature)
+            );
+        }
+
+        #[ink::test]
+        fn set_permissioned_by_non_admin_fails() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                forwarder.set_permissioned(true),
+                Err(AccessControlError::MissingRole)
+            );
+            assert!(!forwarder.is_permissioned());
+        }
+
+        #[ink::test]
+        fn permissioned_forwarder_only_accepts_relayers() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let (secret_key, from) = signer();
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            assert_eq!(forwarder.set_permissioned(true), Ok(()));
+            let mut req = transaction(from);
+            req.nonce = 1;
+            let signature = sign(&forwarder, &req, &secret_key);
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                forwarder.execute(req.clone(), signature.clone()),
+                Err(Error::RelayerNotAllowed)
+            );
+            assert_eq!(
+                forwarder.execute_batch(
+                    vec![(req.clone(), signature.clone())],
+                    BatchMode::BestEffort
+                ),
+                Err(Error::RelayerNotAllowed)
+            );
+
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(forwarder.grant_role(RELAYER, accounts.bob), Ok(()));
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                forwarder.execute(req.clone(), signature.clone()),
+                Err(Error::IncorrectNonce)
+            );
+
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(forwarder.set_permissioned(false), Ok(()));
+            ink::env::test::set_caller::<Environment>(accounts.charlie);
+            assert_eq!(
+                forwarder.execute(req, signature),
+                Err(Error::IncorrectNonce)
+            );
+        }
+
+        #[ink::test]
+        fn pinned_relayer_works() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let (secret_key, from) = signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let mut req = transaction(from);
+            req.relayer = Some(accounts.bob);
+            let signature = sign(&forwarder, &req, &secret_key);
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                forwarder.is_executable(req.clone(), signature.clone()),
+                Ok(())
+            );
+            ink::env::test::set_caller::<Environment>(accounts.charlie);
+            assert_eq!(
+                forwarder.is_executable(req.clone(), signature.clone()),
+                Err(Error::IncorrectRelayer)
+            );
+
+            req.relayer = None;
+            assert_eq!(
+                forwarder.is_executable(req, signature),
+                Err(Error::IncorrectSignature)
+            );
+        }
+
+        #[ink::test]
+        fn calling_forwarder_fails() {
+            let (secret_key, from) = signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let mut req = transaction(from);
+            req.callee = ink::env::test::callee::<Environment>();
+            let signature = sign(&forwarder, &req, &secret_key);
+
+            assert_eq!(
+                forwarder.is_executable(req, signature),
+                Err(Error::CalleeNotAllowed)
+            );
+        }
+
+        #[ink::test]
+        fn restricted_callees_works() {
+            let (secret_key, from) = signer();
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let req = transaction(from);
+            let signature = sign(&forwarder, &req, &secret_key);
+            let other_selector = [0x0; 4];
+
+            assert_eq!(forwarder.set_callees_restricted(true), Ok(()));
+            assert_eq!(
+                forwarder.is_executable(req.clone(), signature.clone()),
+                Err(Error::CalleeNotAllowed)
+            );
+
+            assert_eq!(
+                forwarder.set_callee_allowed(req.callee, Some(req.selector), true),
+                Ok(())
+            );
+            assert_eq!(
+                forwarder.is_executable(req.clone(), signature.clone()),
+                Ok(())
+            );
+            assert!(!forwarder.is_callee_allowed(req.callee, other_selector));
+
+            assert_eq!(forwarder.set_callee_allowed(req.callee, None, true), Ok(()));
+            assert!(forwarder.is_callee_allowed(req.callee, other_selector));
+
+            assert_eq!(
+                forwarder.set_callee_allowed(req.callee, None, false),
+                Ok(())
+            );
+            assert_eq!(
+                forwarder.set_callee_allowed(req.callee, Some(req.selector), false),
+                Ok(())
+            );
+            assert_eq!(
+                forwarder.is_executable(req.clone(), signature.clone()),
+                Err(Error::CalleeNotAllowed)
+            );
+
+            assert_eq!(forwarder.set_callees_restricted(false), Ok(()));
+            assert_eq!(forwarder.is_executable(req, signature), Ok(()));
+        }
+
+        #[ink::test]
+        fn policy_cannot_allow_counterparties() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let (secret_key, from) = signer();
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let fee_collector = AccountId::from([0x5; 32]);
+            let paymaster = AccountId::from([0x7; 32]);
+            assert_eq!(forwarder.set_fee_collector(Some(fee_collector)), Ok(()));
+
+            let mut req = transaction(from);
+            req.paymaster = Some(paymaster);
+
+            for restricted in [false, true] {
+                assert_eq!(forwarder.set_callees_restricted(restricted), Ok(()));
+                for callee in [fee_collector, paymaster] {
+                    assert_eq!(forwarder.set_callee_allowed(callee, None, true), Ok(()));
+                    req.callee = callee;
+                    let signature = sign(&forwarder, &req, &secret_key);
+                    assert_eq!(
+                        forwarder.is_executable(req.clone(), signature),
+                        Err(Error::CalleeNotAllowed)
+                    );
+                }
+
+                // Reserved messages of other paymasters and fee collectors neither
+                req.callee = accounts.charlie;
+                for selector in [
+                    PAYMASTER_VALIDATE_SELECTOR,
+                    PAYMASTER_REIMBURSE_SELECTOR,
+                    FEE_COLLECTOR_COLLECT_SELECTOR,
+                ] {
+                    assert_eq!(
+                        forwarder.set_callee_allowed(req.callee, Some(selector), true),
+                        Ok(())
+                    );
+                    assert!(!forwarder.is_callee_allowed(req.callee, selector));
+                }
+            }
+        }
+
+        #[ink::test]
+        fn transaction_can_call_its_fee_token() {
+            let (secret_key, from) = signer();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+
+            // A gasless PSP22 transfer paying its fee in the transferred token
+            let mut req = transaction(from);
+            req.selector = ink::selector_bytes!("PSP22::transfer");
+            req.fee = Some(Fee {
+                token: req.callee,
+                amount: 100,
+                recipient: None,
+            });
+            let signature = sign(&forwarder, &req, &secret_key);
+            assert_eq!(forwarder.is_executable(req, signature), Ok(()));
+        }
+
+        #[ink::test]
+        fn messages_named_like_reserved_messages_can_be_called() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+
+            // Only the trait messages are reserved, their selectors are namespaced by the trait
+            assert_eq!(
+                PAYMASTER_VALIDATE_SELECTOR,
+                ink::selector_bytes!("Paymaster::validate")
+            );
+            assert_eq!(
+                PAYMASTER_REIMBURSE_SELECTOR,
+                ink::selector_bytes!("Paymaster::reimburse")
+            );
+            assert_eq!(
+                FEE_COLLECTOR_COLLECT_SELECTOR,
+                ink::selector_bytes!("FeeCollector::collect")
+            );
+            for selector in [
+                ink::selector_bytes!("validate"),
+                ink::selector_bytes!("reimburse"),
+                ink::selector_bytes!("collect"),
+            ] {
+                assert!(forwarder.is_callee_allowed(accounts.charlie, selector));
+            }
+        }
+
+        #[ink::test]
+        fn set_callee_policy_by_non_admin_fails() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                forwarder.set_callees_restricted(true),
+                Err(AccessControlError::MissingRole)
+            );
+            assert_eq!(
+                forwarder.set_callee_allowed(accounts.charlie, None, true),
+                Err(AccessControlError::MissingRole)
+            );
+        }
+
+        #[ink::test]
+        fn forwarded_call_cannot_spend_fee_allowances() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let (secret_key, from) = signer();
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let fee_collector = AccountId::from([0x5; 32]);
+            assert_eq!(forwarder.set_fee_collector(Some(fee_collector)), Ok(()));
+            assert_eq!(forwarder.get_fee_collector(), Some(fee_collector));
+
+            // The signer tries to have the fee collector, which the forwarder may call, move django's
+            // fee tokens to itself
+            let mut req = transaction(from);
+            req.callee = fee_collector;
+            req.selector = FEE_COLLECTOR_COLLECT_SELECTOR;
+            req.input = (
+                AccountId::from([0x6; 32]),
+                accounts.django,
+                from,
+                100 as Balance,
+            )
+                .encode();
+            let signature = sign(&forwarder, &req, &secret_key);
+            assert_eq!(
+                forwarder.is_executable(req.clone(), signature.clone()),
+                Err(Error::CalleeNotAllowed)
+            );
+            assert_eq!(
+                forwarder.execute(req, signature),
+                Err(Error::CalleeNotAllowed)
+            );
+            assert_eq!(forwarder.get_nonce(from), 0);
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                forwarder.set_fee_collector(None),
+                Err(AccessControlError::MissingRole)
+            );
+        }
+
+        #[ink::test]
+        fn forwarded_call_cannot_use_paymaster() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let (secret_key, from) = signer();
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            let forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let paymaster = AccountId::from([0x7; 32]);
+
+            // The signer tries to have a paymaster, which trusts the forwarder, pay it its budget
+            let mut req = transaction(from);
+            req.callee = paymaster;
+            req.selector = PAYMASTER_REIMBURSE_SELECTOR;
+            req.input = (from, u64::MAX).encode();
+            let signature = sign(&forwarder, &req, &secret_key);
+            assert_eq!(
+                forwarder.is_executable(req.clone(), signature),
+                Err(Error::CalleeNotAllowed)
+            );
+
+            // or count transactions against the quota of django
+            req.selector = PAYMASTER_VALIDATE_SELECTOR;
+            req.input = (accounts.django, req.callee, req.selector, 1_000_u64).encode();
+            let signature = sign(&forwarder, &req, &secret_key);
+            assert_eq!(
+                forwarder.is_executable(req.clone(), signature),
+                Err(Error::CalleeNotAllowed)
+            );
+
+            // No message of the paymaster of the transaction can be called
+            req.selector = ink::selector_bytes!("fund");
+            req.input = Vec::new();
+            req.paymaster = Some(paymaster);
+            let signature = sign(&forwarder, &req, &secret_key);
+            assert_eq!(
+                forwarder.is_executable(req, signature),
+                Err(Error::CalleeNotAllowed)
+            );
+        }
+
+        #[ink::test]
+        fn upgrade_by_non_admin_fails() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            assert_eq!(forwarder.storage_version(), STORAGE_VERSION);
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                forwarder.set_code_hash([0x1; 32]),
+                Err(Error::AccessControlError(AccessControlError::MissingRole))
+            );
+            assert_eq!(
+                forwarder.migrate(),
+                Err(Error::AccessControlError(AccessControlError::MissingRole))
+            );
+
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(forwarder.migrate(), Ok(()));
+        }
+
+        #[ink::test]
+        fn unmigrated_forwarder_rejects_execution() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let (secret_key, from) = signer();
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let domain_separator = forwarder.domain_separator();
+            let mut req = transaction(from);
+            req.nonce = 1;
+            let signature = sign(&forwarder, &req, &secret_key);
+
+            // Storage left behind by version 1, with its configuration packed into the root cell
+            forwarder.config = ConfigData::default();
+            forwarder.legacy_config = LegacyCell(
+                ConfigV1 {
+                    genesis_hash: GENESIS_HASH.into(),
+                    evm_chain_id: EVM_CHAIN_ID,
+                    storage_version: 1,
+                    permissioned: false,
+                    paused: false,
+                    callees_restricted: true,
+                }
+                .encode(),
+            );
+            assert_eq!(forwarder.storage_version(), 1);
+            assert_eq!(
+                forwarder.execute(req.clone(), signature.clone()),
+                Err(Error::MigrationPending)
+            );
+            assert_eq!(
+                forwarder.execute_batch(
+                    vec![(req.clone(), signature.clone())],
+                    BatchMode::BestEffort
+                ),
+                Err(Error::MigrationPending)
+            );
+
+            // A guardian pause made before the migration is kept
+            assert_eq!(forwarder.pause(), Ok(()));
+            assert_eq!(forwarder.migrate(), Ok(()));
+            assert_eq!(forwarder.storage_version(), STORAGE_VERSION);
+            assert_eq!(forwarder.domain_separator(), domain_separator);
+            assert!(forwarder.is_callees_restricted());
+            assert!(!forwarder.is_permissioned());
+            assert!(forwarder.paused());
+            assert!(forwarder.legacy_config.0.is_empty());
+            assert_eq!(forwarder.migrate(), Ok(()));
+
+            assert_eq!(forwarder.unpause(), Ok(()));
+            assert_eq!(forwarder.set_callees_restricted(false), Ok(()));
+            assert_eq!(
+                forwarder.execute(req, signature),
+                Err(Error::IncorrectNonce)
+            );
+        }
+
+        #[ink::test]
+        fn migrate_rejects_unknown_storage() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+
+            // Storage written by a newer version cannot be converted back
+            forwarder.config.version = STORAGE_VERSION + 1;
+            assert_eq!(forwarder.migrate(), Err(Error::UnsupportedStorageVersion));
+
+            // Nor can a root cell which is not the configuration of version 1
+            forwarder.config = ConfigData::default();
+            forwarder.legacy_config = LegacyCell(vec![0x01, 0x02]);
+            assert_eq!(forwarder.migrate(), Err(Error::UnsupportedStorageVersion));
+            assert_eq!(forwarder.storage_version(), 1);
+        }
+
+        #[ink::test]
+        fn legacy_cell_keeps_the_rest_of_the_root_cell() {
+            let bytes = vec![0x2a; 48];
+            let cell = LegacyCell::decode(&mut &bytes[..]).unwrap();
+            assert_eq!(cell.0, bytes);
+            assert_eq!(cell.encode(), bytes);
+            assert!(LegacyCell::default().encode().is_empty());
+        }
+
+        #[ink::test]
+        fn paused_forwarder_rejects_execution() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let (secret_key, from) = signer();
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            let mut req = transaction(from);
+            req.nonce = 1;
+            let signature = sign(&forwarder, &req, &secret_key);
+
+            assert_eq!(forwarder.pause(), Ok(()));
+            assert!(forwarder.paused());
+            assert_eq!(
+                forwarder.execute(req.clone(), signature.clone()),
+                Err(Error::Paused)
+            );
+            assert_eq!(
+                forwarder.execute_batch(
+                    vec![(req.clone(), signature.clone())],
+                    BatchMode::BestEffort
+                ),
+                Err(Error::Paused)
+            );
+
+            assert_eq!(forwarder.unpause(), Ok(()));
+            assert_eq!(
+                forwarder.execute(req, signature),
+                Err(Error::IncorrectNonce)
+            );
+        }
+
+        #[ink::test]
+        fn only_guardian_pauses_and_only_admin_unpauses() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            let mut forwarder = Forwarder::new(GENESIS_HASH.into(), EVM_CHAIN_ID);
+            assert_eq!(forwarder.grant_role(GUARDIAN, accounts.bob), Ok(()));
+
+            ink::env::test::set_caller::<Environment>(accounts.charlie);
+            assert_eq!(forwarder.pause(), Err(AccessControlError::MissingRole));
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(forwarder.pause(), Ok(()));
+            assert_eq!(forwarder.unpause(), Err(AccessControlError::MissingRole));
+            assert!(forwarder.paused());
         }
     }
 }
@@ -0,0 +1,208 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+/// A wallet owned by a set of ECDSA keys, a threshold of which must approve every signature made on its behalf.
+///
+/// It cannot sign itself, so a forwarder asks it through `ContractSigner::is_valid_signature` whether it
+/// approves a meta transaction signed with `Signature::Contract`.
+#[ink::contract]
+mod multisig {
+    use contract_signer::ContractSigner;
+    use ink::prelude::vec::Vec;
+    use scale::DecodeAll;
+
+    #[ink(storage)]
+    pub struct Multisig {
+        /// Accounts of the owner keys, derived from their compressed ECDSA public keys like Substrate does.
+        owners: Vec<AccountId>,
+        /// Number of distinct owners that must approve a signature.
+        threshold: u32,
+    }
+
+    /// Errors that can occur upon calling this contract.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub enum Error {
+        /// The threshold is zero or exceeds the number of owners
+        InvalidThreshold,
+        /// An owner is listed twice
+        DuplicateOwner,
+    }
+
+    impl Multisig {
+        /// Create a wallet approving signatures of at least `threshold` of `owners`.
+        #[ink(constructor)]
+        pub fn new(owners: Vec<AccountId>, threshold: u32) -> Result<Self, Error> {
+            if threshold == 0 || threshold as usize > owners.len() {
+                return Err(Error::InvalidThreshold)
+            }
+            for (index, owner) in owners.iter().enumerate() {
+                if owners[..index].contains(owner) {
+                    return Err(Error::DuplicateOwner)
+                }
+            }
+            Ok(Self { owners, threshold })
+        }
+
+        #[ink(message)]
+        pub fn get_owners(&self) -> Vec<AccountId> {
+            self.owners.clone()
+        }
+
+        #[ink(message)]
+        pub fn get_threshold(&self) -> u32 {
+            self.threshold
+        }
+
+        /// Returns the digest owners sign to approve `hash`: `blake2x256(SCALE((wallet, hash)))`.
+        /// Binding it to the wallet keeps approvals of owners shared with other wallets from being reused.
+        #[ink(message)]
+        pub fn approval_hash(&self, hash: [u8; 32]) -> [u8; 32] {
+            use ink::env::hash;
+
+            let mut output = <hash::Blake2x256 as hash::HashOutput>::Type::default();
+            ink::env::hash_encoded::<hash::Blake2x256, _>(
+                &(self.env().account_id(), hash),
+                &mut output,
+            );
+            output
+        }
+
+        /// Convert a compressed 33 byte ECDSA public key into a 32 byte Substrate address
+        fn to_default_account_id(compressed_pub_key: [u8; 33]) -> AccountId {
+            use ink::env::hash;
+
+            let mut output = <hash::Blake2x256 as hash::HashOutput>::Type::default();
+            ink::env::hash_bytes::<hash::Blake2x256>(&compressed_pub_key[..], &mut output);
+
+            output.into()
+        }
+    }
+
+    impl ContractSigner for Multisig {
+        /// `signature` is the SCALE encoded `Vec<[u8; 65]>` of the owners' ECDSA signatures of
+        /// `approval_hash(hash)`. Signatures of other keys or repeated owners invalidate it.
+        #[ink(message)]
+        fn is_valid_signature(&self, hash: [u8; 32], signature: Vec<u8>) -> bool {
+            let Ok(signatures) = Vec::<[u8; 65]>::decode_all(&mut &signature[..]) else {
+                return false
+            };
+            let approval_hash = self.approval_hash(hash);
+
+            let mut approvals: Vec<AccountId> = Vec::with_capacity(signatures.len());
+            for signature in signatures {
+                let Ok(pub_key) = self.env().ecdsa_recover(&signature, &approval_hash) else {
+                    return false
+                };
+                let owner = Self::to_default_account_id(pub_key);
+                if !self.owners.contains(&owner) || approvals.contains(&owner) {
+                    return false
+                }
+                approvals.push(owner);
+            }
+            approvals.len() >= self.threshold as usize
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use scale::Encode;
+        use secp256k1::{
+            Message,
+            SecretKey,
+            SECP256K1,
+        };
+
+        const HASH: [u8; 32] = [0x42; 32];
+
+        fn owner(seed: u8) -> (SecretKey, AccountId) {
+            let secret_key = SecretKey::from_slice(&[seed; 32]).unwrap();
+            let public_key = secret_key.public_key(SECP256K1).serialize();
+            (secret_key, Multisig::to_default_account_id(public_key))
+        }
+
+        fn sign_hash(message_hash: [u8; 32], secret_key: &SecretKey) -> [u8; 65] {
+            let message = Message::from_slice(&message_hash).unwrap();
+            let (recovery_id, compact) = SECP256K1
+                .sign_ecdsa_recoverable(&message, secret_key)
+                .serialize_compact();
+            let mut signature = [0; 65];
+            signature[..64].copy_from_slice(&compact);
+            signature[64] = recovery_id.to_i32() as u8;
+            signature
+        }
+
+        /// Approve `hash` on behalf of `multisig` with the keys of `seeds`.
+        fn approve(multisig: &Multisig, hash: [u8; 32], seeds: &[u8]) -> Vec<u8> {
+            let approval_hash = multisig.approval_hash(hash);
+            seeds
+                .iter()
+                .map(|seed| sign_hash(approval_hash, &owner(*seed).0))
+                .collect::<Vec<_>>()
+                .encode()
+        }
+
+        /// A 2 out of 3 wallet owned by the keys of seeds 1, 2 and 3.
+        fn wallet() -> Multisig {
+            Multisig::new(vec![owner(1).1, owner(2).1, owner(3).1], 2).unwrap()
+        }
+
+        #[ink::test]
+        fn new_checks_owners_and_threshold() {
+            assert_eq!(
+                Multisig::new(vec![owner(1).1], 0).err(),
+                Some(Error::InvalidThreshold)
+            );
+            assert_eq!(
+                Multisig::new(vec![owner(1).1], 2).err(),
+                Some(Error::InvalidThreshold)
+            );
+            assert_eq!(
+                Multisig::new(vec![owner(1).1, owner(1).1], 1).err(),
+                Some(Error::DuplicateOwner)
+            );
+
+            let multisig = wallet();
+            assert_eq!(multisig.get_owners().len(), 3);
+            assert_eq!(multisig.get_threshold(), 2);
+        }
+
+        #[ink::test]
+        fn threshold_of_owners_is_valid() {
+            let multisig = wallet();
+            assert!(multisig.is_valid_signature(HASH, approve(&multisig, HASH, &[1, 3])));
+            assert!(multisig.is_valid_signature(HASH, approve(&multisig, HASH, &[3, 2, 1])));
+        }
+
+        #[ink::test]
+        fn too_few_owners_are_invalid() {
+            let multisig = wallet();
+            assert!(!multisig.is_valid_signature(HASH, approve(&multisig, HASH, &[2])));
+            assert!(!multisig.is_valid_signature(HASH, approve(&multisig, HASH, &[2, 2])));
+        }
+
+        #[ink::test]
+        fn signature_of_non_owner_is_invalid() {
+            let multisig = wallet();
+            assert!(!multisig.is_valid_signature(HASH, approve(&multisig, HASH, &[1, 2, 4])));
+        }
+
+        #[ink::test]
+        fn signature_of_other_hash_or_wallet_is_invalid() {
+            let multisig = wallet();
+            let signature = approve(&multisig, HASH, &[1, 2]);
+            assert!(!multisig.is_valid_signature([0x43; 32], signature.clone()));
+
+            ink::env::test::set_callee::<Environment>(AccountId::from([0x7; 32]));
+            let other_multisig = wallet();
+            assert!(!other_multisig.is_valid_signature(HASH, signature));
+        }
+
+        #[ink::test]
+        fn malformed_signature_is_invalid() {
+            let multisig = wallet();
+            assert!(!multisig.is_valid_signature(HASH, vec![]));
+            assert!(!multisig.is_valid_signature(HASH, vec![0x4, 0x1, 0x2]));
+        }
+    }
+}
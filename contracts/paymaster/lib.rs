@@ -0,0 +1,376 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+#![feature(min_specialization)]
+
+/// Sponsors meta transactions submitted through a forwarder.
+///
+/// The forwarder calls `Paymaster::validate` before it executes a transaction naming this paymaster and
+/// `Paymaster::reimburse` afterwards, paying the relayer for the gas it spent out of the paymaster's balance.
+/// Both only check that they are called by the forwarder, which refuses to forward transactions calling them.
+#[ink::contract]
+mod paymaster {
+    use ink::storage::Mapping;
+    use openbrush::{
+        contracts::access_control::*,
+        modifiers,
+        traits::Storage,
+    };
+    use paymaster_trait::PaymasterError;
+
+    /// Selector of a message of a sponsored callee.
+    pub type Selector = [u8; 4];
+
+    /// Milliseconds in a day, the period of the per-user quota.
+    const MILLISECONDS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+    #[ink(storage)]
+    #[derive(Storage)]
+    pub struct Paymaster {
+        #[storage_field]
+        access_control: access_control::Data,
+
+        /// The only forwarder allowed to consume the budget.
+        forwarder: AccountId,
+        /// Maximum gas a sponsored transaction may use, bounding both its gas limit and the reimbursement.
+        max_gas: u64,
+        /// Number of sponsored transactions a user may execute per day.
+        daily_quota: u32,
+        /// Callees whose every message is sponsored.
+        sponsored_callees: Mapping<AccountId, ()>,
+        /// Sponsored messages, keyed by callee and selector.
+        sponsored_selectors: Mapping<(AccountId, Selector), ()>,
+        /// Sponsored transactions of every user, keyed by user and day.
+        usage: Mapping<(AccountId, u64), u32>,
+    }
+
+    /// `from` added `amount` to the budget
+    #[ink(event)]
+    pub struct Funded {
+        #[ink(topic)]
+        from: AccountId,
+        amount: Balance,
+    }
+
+    /// `relayer` was paid `amount` for a sponsored transaction
+    #[ink(event)]
+    pub struct Reimbursed {
+        #[ink(topic)]
+        relayer: AccountId,
+        amount: Balance,
+    }
+
+    /// Errors that can occur upon calling this contract.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub enum Error {
+        /// The budget cannot cover the withdrawal
+        InsufficientBudget,
+        /// Transferring balance out of the paymaster failed
+        TransferFailed,
+        AccessControlError(AccessControlError),
+    }
+
+    impl From<AccessControlError> for Error {
+        fn from(err: AccessControlError) -> Self {
+            Error::AccessControlError(err)
+        }
+    }
+
+    impl AccessControl for Paymaster {}
+
+    impl Paymaster {
+        /// Creates a paymaster sponsoring transactions of `forwarder`, administered by the caller.
+        #[ink(constructor)]
+        pub fn new(forwarder: AccountId, max_gas: u64, daily_quota: u32) -> Self {
+            let mut _instance = Self {
+                access_control: Default::default(),
+                forwarder,
+                max_gas,
+                daily_quota,
+                sponsored_callees: Mapping::default(),
+                sponsored_selectors: Mapping::default(),
+                usage: Mapping::default(),
+            };
+            _instance._init_with_admin(_instance.env().caller());
+            _instance
+        }
+
+        /// Add the tokens sent with this call to the budget.
+        #[ink(message, payable)]
+        pub fn fund(&mut self) {
+            self.env().emit_event(Funded {
+                from: self.env().caller(),
+                amount: self.env().transferred_value(),
+            });
+        }
+
+        /// Returns the budget left to reimburse relayers.
+        #[ink(message)]
+        pub fn budget(&self) -> Balance {
+            self.env().balance()
+        }
+
+        /// Withdraw `amount` of the budget to the caller. Admin only.
+        #[ink(message)]
+        #[modifiers(only_role(DEFAULT_ADMIN_ROLE))]
+        pub fn withdraw(&mut self, amount: Balance) -> Result<(), Error> {
+            if self.budget() < amount {
+                return Err(Error::InsufficientBudget)
+            }
+            self.env()
+                .transfer(self.env().caller(), amount)
+                .map_err(|_| Error::TransferFailed)
+        }
+
+        /// Sponsor or stop sponsoring calls to `selector` of `callee`, or every message of `callee` if `selector` is `None`. Admin only.
+        #[ink(message)]
+        #[modifiers(only_role(DEFAULT_ADMIN_ROLE))]
+        pub fn set_sponsored(
+            &mut self,
+            callee: AccountId,
+            selector: Option<Selector>,
+            sponsored: bool,
+        ) -> Result<(), Error> {
+            match (selector, sponsored) {
+                (Some(selector), true) => {
+                    self.sponsored_selectors.insert((callee, selector), &());
+                }
+                (Some(selector), false) => self.sponsored_selectors.remove((callee, selector)),
+                (None, true) => {
+                    self.sponsored_callees.insert(callee, &());
+                }
+                (None, false) => self.sponsored_callees.remove(callee),
+            }
+            Ok(())
+        }
+
+        /// Returns whether calls to `selector` of `callee` are sponsored.
+        #[ink(message)]
+        pub fn is_sponsored(&self, callee: AccountId, selector: Selector) -> bool {
+            self.sponsored_callees.contains(callee)
+                || self.sponsored_selectors.contains((callee, selector))
+        }
+
+        /// Set the maximum gas of a sponsored transaction. Admin only.
+        #[ink(message)]
+        #[modifiers(only_role(DEFAULT_ADMIN_ROLE))]
+        pub fn set_max_gas(&mut self, max_gas: u64) -> Result<(), Error> {
+            self.max_gas = max_gas;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_max_gas(&self) -> u64 {
+            self.max_gas
+        }
+
+        /// Set the number of sponsored transactions a user may execute per day. Admin only.
+        #[ink(message)]
+        #[modifiers(only_role(DEFAULT_ADMIN_ROLE))]
+        pub fn set_daily_quota(&mut self, daily_quota: u32) -> Result<(), Error> {
+            self.daily_quota = daily_quota;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_daily_quota(&self) -> u32 {
+            self.daily_quota
+        }
+
+        /// Returns the number of sponsored transactions `user` executed today.
+        #[ink(message)]
+        pub fn get_usage(&self, user: AccountId) -> u32 {
+            self.usage.get((user, self.today())).unwrap_or(0)
+        }
+
+        fn ensure_forwarder(&self) -> Result<(), PaymasterError> {
+            if self.env().caller() != self.forwarder {
+                return Err(PaymasterError::CallerNotForwarder)
+            }
+            Ok(())
+        }
+
+        fn today(&self) -> u64 {
+            self.env().block_timestamp() / MILLISECONDS_PER_DAY
+        }
+    }
+
+    impl paymaster_trait::Paymaster for Paymaster {
+        /// Sponsors transactions of allowed callees and selectors with a gas limit of at most `max_gas`,
+        /// up to `daily_quota` per user and day. Forwarder only.
+        #[ink(message)]
+        fn validate(
+            &mut self,
+            from: AccountId,
+            callee: AccountId,
+            selector: Selector,
+            gas_limit: u64,
+        ) -> Result<(), PaymasterError> {
+            self.ensure_forwarder()?;
+            if !self.is_sponsored(callee, selector) {
+                return Err(PaymasterError::NotSponsored)
+            }
+            if gas_limit > self.max_gas {
+                return Err(PaymasterError::GasLimitTooHigh)
+            }
+            let usage = self.get_usage(from);
+            if usage >= self.daily_quota {
+                return Err(PaymasterError::QuotaExceeded)
+            }
+            self.usage.insert((from, self.today()), &(usage + 1));
+            Ok(())
+        }
+
+        /// Pays the fee of `gas_used`, capped at `max_gas`. Forwarder only.
+        #[ink(message)]
+        fn reimburse(
+            &mut self,
+            relayer: AccountId,
+            gas_used: u64,
+        ) -> Result<Balance, PaymasterError> {
+            self.ensure_forwarder()?;
+            let amount = self.env().weight_to_fee(gas_used.min(self.max_gas));
+            if self.budget() < amount {
+                return Err(PaymasterError::InsufficientBudget)
+            }
+            self.env()
+                .transfer(relayer, amount)
+                .map_err(|_| PaymasterError::TransferFailed)?;
+            self.env().emit_event(Reimbursed { relayer, amount });
+            Ok(amount)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use paymaster_trait::Paymaster as _;
+
+        const SELECTOR: Selector = [0x63, 0x3a, 0xa5, 0x51];
+
+        fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
+            ink::env::test::default_accounts::<Environment>()
+        }
+
+        /// A paymaster administered by alice for the forwarder bob, sponsoring `SELECTOR` of charlie.
+        fn paymaster() -> Paymaster {
+            let accounts = default_accounts();
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            let mut paymaster = Paymaster::new(accounts.bob, 1_000, 2);
+            assert_eq!(
+                paymaster.set_sponsored(accounts.charlie, Some(SELECTOR), true),
+                Ok(())
+            );
+            paymaster
+        }
+
+        #[ink::test]
+        fn validate_works() {
+            let accounts = default_accounts();
+            let mut paymaster = paymaster();
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                paymaster.validate(accounts.django, accounts.charlie, SELECTOR, 1_000),
+                Ok(())
+            );
+            assert_eq!(paymaster.get_usage(accounts.django), 1);
+        }
+
+        #[ink::test]
+        fn validate_by_non_forwarder_fails() {
+            let accounts = default_accounts();
+            let mut paymaster = paymaster();
+
+            assert_eq!(
+                paymaster.validate(accounts.django, accounts.charlie, SELECTOR, 1_000),
+                Err(PaymasterError::CallerNotForwarder)
+            );
+        }
+
+        #[ink::test]
+        fn validate_checks_policy() {
+            let accounts = default_accounts();
+            let mut paymaster = paymaster();
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                paymaster.validate(accounts.django, accounts.charlie, [0x0; 4], 1_000),
+                Err(PaymasterError::NotSponsored)
+            );
+            assert_eq!(
+                paymaster.validate(accounts.django, accounts.charlie, SELECTOR, 1_001),
+                Err(PaymasterError::GasLimitTooHigh)
+            );
+
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(
+                paymaster.set_sponsored(accounts.charlie, None, true),
+                Ok(())
+            );
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                paymaster.validate(accounts.django, accounts.charlie, [0x0; 4], 1_000),
+                Ok(())
+            );
+        }
+
+        #[ink::test]
+        fn validate_checks_daily_quota() {
+            let accounts = default_accounts();
+            let mut paymaster = paymaster();
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            for _ in 0..2 {
+                assert_eq!(
+                    paymaster.validate(accounts.django, accounts.charlie, SELECTOR, 1_000),
+                    Ok(())
+                );
+            }
+            assert_eq!(
+                paymaster.validate(accounts.django, accounts.charlie, SELECTOR, 1_000),
+                Err(PaymasterError::QuotaExceeded)
+            );
+            assert_eq!(
+                paymaster.validate(accounts.eve, accounts.charlie, SELECTOR, 1_000),
+                Ok(())
+            );
+
+            ink::env::test::set_block_timestamp::<Environment>(MILLISECONDS_PER_DAY);
+            assert_eq!(
+                paymaster.validate(accounts.django, accounts.charlie, SELECTOR, 1_000),
+                Ok(())
+            );
+        }
+
+        #[ink::test]
+        fn reimburse_works() {
+            let accounts = default_accounts();
+            let mut paymaster = paymaster();
+            ink::env::test::set_account_balance::<Environment>(
+                ink::env::test::callee::<Environment>(),
+                1_000_000,
+            );
+            ink::env::test::set_account_balance::<Environment>(accounts.frank, 0);
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            let amount = paymaster.reimburse(accounts.frank, 5_000).unwrap();
+            assert_eq!(amount, ink::env::weight_to_fee::<Environment>(1_000));
+            assert_eq!(
+                ink::env::test::get_account_balance::<Environment>(accounts.frank),
+                Ok(amount)
+            );
+        }
+
+        #[ink::test]
+        fn set_sponsored_by_non_admin_fails() {
+            let accounts = default_accounts();
+            let mut paymaster = paymaster();
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                paymaster.set_sponsored(accounts.charlie, None, true),
+                Err(Error::AccessControlError(AccessControlError::MissingRole))
+            );
+        }
+    }
+}
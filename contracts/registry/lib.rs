@@ -1,13 +1,15 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 #![feature(min_specialization)]
 
+#[meta_tx_context::meta_tx_contract]
 #[ink::contract]
 mod registry {
     use ink::{
-        prelude::{
-            string::String,
-            vec::Vec,
+        codegen::{
+            EmitEvent,
+            Env,
         },
+        prelude::string::String,
         storage::Mapping,
     };
     use meta_tx_context::*;
@@ -45,27 +47,60 @@ mod registry {
     }
 
     impl AccessControl for Registry {}
-    impl MetaTxContext for Registry {}
+
+    /// The set of trusted forwarders is going to change once `eta` is reached
+    #[ink(event)]
+    pub struct TrustedForwarderProposed {
+        forwarder: TrustedForwarder,
+        eta: Timestamp,
+    }
+
+    /// `forwarder` is now trusted, or no longer trusted
+    #[ink(event)]
+    pub struct TrustedForwarderChanged {
+        forwarder: TrustedForwarder,
+        trusted: bool,
+    }
+
+    impl MetaTxContext for Registry {
+        fn _emit_trusted_forwarder_proposed_event(
+            &self,
+            forwarder: TrustedForwarder,
+            eta: Timestamp,
+        ) {
+            self.env()
+                .emit_event(TrustedForwarderProposed { forwarder, eta });
+        }
+
+        fn _emit_trusted_forwarder_changed_event(
+            &self,
+            forwarder: TrustedForwarder,
+            trusted: bool,
+        ) {
+            self.env()
+                .emit_event(TrustedForwarderChanged { forwarder, trusted });
+        }
+    }
 
     impl Registry {
         #[ink(constructor)]
         pub fn new(trusted_forwarder: AccountId) -> Self {
             let mut _instance = Self::default();
             _instance._init_with_admin(_instance.env().caller());
-            _instance
-                .set_trusted_forwarder(trusted_forwarder)
-                .expect("Should have MANAGER role");
+            _instance._setup_role(MANAGER, _instance.env().caller());
+            _instance._setup_role(GUARDIAN, _instance.env().caller());
+            _instance._add_trusted_forwarder(TrustedForwarder::Account(trusted_forwarder));
             _instance
         }
 
         #[ink(message)]
-        pub fn register(&mut self, name: String, data: Vec<u8>) -> Result<(), Error> {
+        #[meta_tx]
+        pub fn register(&mut self, name: String) -> Result<(), Error> {
             ink::env::debug_println!("Register called");
 
             if self.owners.contains(name.clone()) {
                 return Err(Error::NameTaken)
             };
-            let caller = self._caller(data)?;
             if self.names.contains(caller) {
                 return Err(Error::AlreadyRegistered)
             }
@@ -77,8 +112,8 @@ mod registry {
         }
 
         #[ink(message)]
-        pub fn unregister(&mut self, data: Vec<u8>) -> Result<(), Error> {
-            let caller = self._caller(data)?;
+        #[meta_tx]
+        pub fn unregister(&mut self) -> Result<(), Error> {
             let name = self.names.get(caller).ok_or(Error::NameNotRegistered)?;
 
             self.names.remove(&caller);
@@ -101,18 +136,27 @@ mod registry {
     #[cfg(test)]
     mod tests {
         use super::*;
+        use scale::Encode;
 
         fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
             ink::env::test::default_accounts::<Environment>()
         }
 
+        /// Append the relayer frank and `signer` to the input of the current call, as a forwarder would.
+        fn forward_for(signer: AccountId) {
+            let mut input = vec![0x0; 4];
+            input.extend_from_slice(default_accounts().frank.as_ref());
+            input.extend_from_slice(signer.as_ref());
+            meta_tx_context::test::set_call_input(input);
+        }
+
         #[ink::test]
         fn register_works() {
             let accounts = default_accounts();
             ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
 
             let mut registry = Registry::new([0x0; 32].into());
-            assert_eq!(registry.register(String::from("alice"), vec![]), Ok(()));
+            assert_eq!(registry.register(String::from("alice")), Ok(()));
             assert_eq!(
                 registry.get_owner(String::from("alice")),
                 Some(accounts.alice)
@@ -129,9 +173,9 @@ mod registry {
             ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
 
             let mut registry = Registry::new([0x0; 32].into());
-            assert_eq!(registry.register(String::from("alice"), vec![]), Ok(()));
+            assert_eq!(registry.register(String::from("alice")), Ok(()));
             assert_eq!(
-                registry.register(String::from("alice_2"), vec![]),
+                registry.register(String::from("alice_2")),
                 Err(Error::AlreadyRegistered)
             );
         }
@@ -142,11 +186,11 @@ mod registry {
             ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
 
             let mut registry = Registry::new([0x0; 32].into());
-            assert_eq!(registry.register(String::from("test"), vec![]), Ok(()));
+            assert_eq!(registry.register(String::from("test")), Ok(()));
 
             ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
             assert_eq!(
-                registry.register(String::from("test"), vec![]),
+                registry.register(String::from("test")),
                 Err(Error::NameTaken)
             );
         }
@@ -157,8 +201,8 @@ mod registry {
             ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
 
             let mut registry = Registry::new([0x0; 32].into());
-            assert_eq!(registry.register(String::from("alice"), vec![]), Ok(()));
-            assert_eq!(registry.unregister(vec![]), Ok(()));
+            assert_eq!(registry.register(String::from("alice")), Ok(()));
+            assert_eq!(registry.unregister(), Ok(()));
             assert_eq!(registry.get_name(accounts.alice), None);
         }
 
@@ -168,34 +212,500 @@ mod registry {
             ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
 
             let mut registry = Registry::new([0x0; 32].into());
-            assert_eq!(registry.unregister(vec![]), Err(Error::NameNotRegistered));
+            assert_eq!(registry.unregister(), Err(Error::NameNotRegistered));
+        }
+
+        /// Propose `forwarder` and accept it once the delay has passed.
+        fn trust(registry: &mut Registry, forwarder: TrustedForwarder) {
+            assert_eq!(registry.propose_trusted_forwarder(forwarder), Ok(()));
+            let (_, eta) = registry.get_pending_trusted_forwarder().unwrap();
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(eta);
+            assert_eq!(registry.accept_trusted_forwarder(), Ok(()));
+        }
+
+        #[ink::test]
+        fn accept_trusted_forwarder_works() {
+            let accounts = default_accounts();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            let mut registry = Registry::new([0x0; 32].into());
+            trust(&mut registry, TrustedForwarder::Account([0x1; 32].into()));
+            trust(&mut registry, TrustedForwarder::Account([0x1; 32].into()));
+            assert_eq!(
+                registry.get_trusted_forwarders(),
+                vec![AccountId::from([0x0; 32]), AccountId::from([0x1; 32])]
+            );
+            assert!(registry.is_trusted_forwarder([0x0; 32].into()));
+            assert!(registry.is_trusted_forwarder([0x1; 32].into()));
+            assert_eq!(registry.get_pending_trusted_forwarder(), None);
+            // Accepting an already trusted forwarder does not emit a second change
+            assert_eq!(ink::env::test::recorded_events().count(), 4);
+        }
+
+        #[ink::test]
+        fn accept_trusted_forwarder_before_delay_fails() {
+            let accounts = default_accounts();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            let mut registry = Registry::new([0x0; 32].into());
+            let delay = MIN_TRUSTED_FORWARDER_DELAY + 1_000;
+            assert_eq!(registry.set_trusted_forwarder_delay(delay), Ok(()));
+            assert_eq!(
+                registry.propose_trusted_forwarder(TrustedForwarder::Account(accounts.eve)),
+                Ok(())
+            );
+            assert_eq!(
+                registry.get_pending_trusted_forwarder(),
+                Some((TrustedForwarder::Account(accounts.eve), delay))
+            );
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(delay - 1);
+            assert_eq!(
+                registry.accept_trusted_forwarder(),
+                Err(meta_tx_context::Error::TrustedForwarderDelayNotPassed)
+            );
+            assert!(!registry.is_trusted_forwarder(accounts.eve));
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(delay);
+            assert_eq!(registry.accept_trusted_forwarder(), Ok(()));
+            assert!(registry.is_trusted_forwarder(accounts.eve));
+        }
+
+        #[ink::test]
+        fn cancel_trusted_forwarder_proposal_works() {
+            let accounts = default_accounts();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            let mut registry = Registry::new([0x0; 32].into());
+            assert_eq!(
+                registry.cancel_trusted_forwarder_proposal(),
+                Err(meta_tx_context::Error::NoPendingTrustedForwarder)
+            );
+            assert_eq!(
+                registry.propose_trusted_forwarder(TrustedForwarder::Account(accounts.eve)),
+                Ok(())
+            );
+            assert_eq!(registry.cancel_trusted_forwarder_proposal(), Ok(()));
+            assert_eq!(
+                registry.accept_trusted_forwarder(),
+                Err(meta_tx_context::Error::NoPendingTrustedForwarder)
+            );
+            assert!(!registry.is_trusted_forwarder(accounts.eve));
+        }
+
+        #[ink::test]
+        fn trusted_forwarder_delay_cannot_shrink() {
+            let accounts = default_accounts();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            let mut registry = Registry::new([0x0; 32].into());
+            assert_eq!(
+                registry.get_trusted_forwarder_delay(),
+                MIN_TRUSTED_FORWARDER_DELAY
+            );
+            assert_eq!(
+                registry.set_trusted_forwarder_delay(MIN_TRUSTED_FORWARDER_DELAY - 1),
+                Err(meta_tx_context::Error::TrustedForwarderDelayTooShort)
+            );
+            let delay = MIN_TRUSTED_FORWARDER_DELAY + 1_000;
+            assert_eq!(registry.set_trusted_forwarder_delay(delay), Ok(()));
+            assert_eq!(
+                registry.set_trusted_forwarder_delay(delay - 1),
+                Err(meta_tx_context::Error::TrustedForwarderDelayTooShort)
+            );
+            assert_eq!(registry.get_trusted_forwarder_delay(), delay);
+        }
+
+        #[ink::test]
+        fn trusted_forwarder_delay_is_bounded() {
+            let accounts = default_accounts();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            let mut registry = Registry::new([0x0; 32].into());
+            assert_eq!(
+                registry.set_trusted_forwarder_delay(u64::MAX),
+                Err(meta_tx_context::Error::TrustedForwarderDelayTooLong)
+            );
+            assert_eq!(
+                registry.set_trusted_forwarder_delay(MAX_TRUSTED_FORWARDER_DELAY),
+                Ok(())
+            );
+
+            // Delays stored by earlier versions are brought within the bounds
+            registry.meta_tx_context.trusted_forwarder_delay = u64::MAX;
+            assert_eq!(
+                registry.get_trusted_forwarder_delay(),
+                MAX_TRUSTED_FORWARDER_DELAY
+            );
+            registry.meta_tx_context.trusted_forwarder_delay = 0;
+            assert_eq!(
+                registry.get_trusted_forwarder_delay(),
+                MIN_TRUSTED_FORWARDER_DELAY
+            );
+            assert_eq!(
+                registry.propose_trusted_forwarder(TrustedForwarder::Account(accounts.eve)),
+                Ok(())
+            );
+            assert_eq!(
+                registry.get_pending_trusted_forwarder(),
+                Some((
+                    TrustedForwarder::Account(accounts.eve),
+                    MIN_TRUSTED_FORWARDER_DELAY
+                ))
+            );
+        }
+
+        #[ink::test]
+        fn remove_trusted_forwarder_by_admin_works() {
+            let accounts = default_accounts();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            let mut registry = Registry::new(accounts.django);
+            trust(&mut registry, TrustedForwarder::Account(accounts.eve));
+            assert_eq!(registry.remove_trusted_forwarder(accounts.django), Ok(()));
+            assert!(!registry.is_trusted_forwarder(accounts.django));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            forward_for(accounts.bob);
+            assert_eq!(registry.register(String::from("bob")), Ok(()));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            assert_eq!(registry.register(String::from("django")), Ok(()));
+            assert_eq!(registry.get_owner(String::from("bob")), Some(accounts.bob));
+            assert_eq!(
+                registry.get_owner(String::from("django")),
+                Some(accounts.django)
+            );
+        }
+
+        #[ink::test]
+        fn clear_trusted_forwarders_works() {
+            let accounts = default_accounts();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            let mut registry = Registry::new(accounts.django);
+            trust(&mut registry, TrustedForwarder::CodeHash([0x1; 32].into()));
+            assert_eq!(registry.clear_trusted_forwarders(), Ok(()));
+            assert_eq!(registry.get_trusted_forwarders(), vec![]);
+            assert_eq!(registry.get_trusted_code_hashes(), vec![]);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            assert_eq!(registry.register(String::from("django")), Ok(()));
+            assert_eq!(
+                registry.get_owner(String::from("django")),
+                Some(accounts.django)
+            );
+        }
+
+        #[ink::test]
+        fn legacy_trusted_forwarder_works() {
+            let accounts = default_accounts();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            // Storage written by a version trusting a single forwarder
+            let mut registry = Registry::new(accounts.django);
+            registry.meta_tx_context.trusted_forwarder = Some(accounts.eve);
+            assert_eq!(
+                registry.get_trusted_forwarders(),
+                vec![accounts.eve, accounts.django]
+            );
+            assert!(registry.is_trusted_forwarder(accounts.eve));
+
+            // It is not trusted twice
+            trust(&mut registry, TrustedForwarder::Account(accounts.eve));
+            assert_eq!(
+                registry.meta_tx_context.trusted_forwarders,
+                vec![accounts.django]
+            );
+
+            assert_eq!(registry.remove_trusted_forwarder(accounts.eve), Ok(()));
+            assert!(!registry.is_trusted_forwarder(accounts.eve));
+            assert_eq!(registry.get_trusted_forwarders(), vec![accounts.django]);
+
+            registry.meta_tx_context.trusted_forwarder = Some(accounts.eve);
+            assert_eq!(registry.clear_trusted_forwarders(), Ok(()));
+            assert_eq!(registry.get_trusted_forwarders(), vec![]);
+        }
+
+        #[ink::test]
+        fn manage_trusted_forwarders_by_non_admin_fails() {
+            let accounts = default_accounts();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            let mut registry = Registry::new([0x0; 32].into());
+            assert_eq!(
+                registry.propose_trusted_forwarder(TrustedForwarder::Account(accounts.eve)),
+                Ok(())
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                registry.propose_trusted_forwarder(TrustedForwarder::Account(accounts.bob)),
+                Err(AccessControlError::MissingRole)
+            );
+            assert_eq!(
+                registry.accept_trusted_forwarder(),
+                Err(meta_tx_context::Error::AccessControlError(
+                    AccessControlError::MissingRole
+                ))
+            );
+            assert_eq!(
+                registry.cancel_trusted_forwarder_proposal(),
+                Err(meta_tx_context::Error::AccessControlError(
+                    AccessControlError::MissingRole
+                ))
+            );
+            assert_eq!(
+                registry.set_trusted_forwarder_delay(1_000),
+                Err(meta_tx_context::Error::AccessControlError(
+                    AccessControlError::MissingRole
+                ))
+            );
+            assert_eq!(
+                registry.remove_trusted_forwarder([0x0; 32].into()),
+                Err(AccessControlError::MissingRole)
+            );
+            assert_eq!(
+                registry.remove_trusted_code_hash([0x0; 32].into()),
+                Err(AccessControlError::MissingRole)
+            );
+            assert_eq!(
+                registry.clear_trusted_forwarders(),
+                Err(AccessControlError::MissingRole)
+            );
+        }
+
+        #[ink::test]
+        fn trusted_code_hashes_works() {
+            let accounts = default_accounts();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            let mut registry = Registry::new([0x0; 32].into());
+            trust(&mut registry, TrustedForwarder::CodeHash([0x1; 32].into()));
+            trust(&mut registry, TrustedForwarder::CodeHash([0x2; 32].into()));
+            assert_eq!(registry.remove_trusted_code_hash([0x1; 32].into()), Ok(()));
+            assert_eq!(
+                registry.get_trusted_code_hashes(),
+                vec![Hash::from([0x2; 32])]
+            );
+        }
+
+        #[ink::test]
+        fn register_through_trusted_forwarder_works() {
+            let accounts = default_accounts();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            let mut registry = Registry::new(accounts.django);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            forward_for(accounts.bob);
+            assert_eq!(registry.register(String::from("bob")), Ok(()));
+            assert_eq!(registry.get_owner(String::from("bob")), Some(accounts.bob));
+        }
+
+        #[ink::test]
+        fn caller_with_forged_data_fails() {
+            let accounts = default_accounts();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            let registry = Registry::new(accounts.django);
+
+            // eve signed the transaction but put bob in the signed input as the `data` argument
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            forward_for(accounts.eve);
+            assert_eq!(
+                registry._caller(accounts.bob.encode()),
+                Err(meta_tx_context::Error::SenderMismatch)
+            );
+            assert_eq!(registry._caller(accounts.eve.encode()), Ok(accounts.eve));
         }
 
         #[ink::test]
-        fn set_trusted_forwarder_by_admin_works() {
+        fn pause_meta_tx_falls_back_to_caller() {
+            let accounts = default_accounts();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            let mut registry = Registry::new(accounts.django);
+            assert_eq!(registry.pause_meta_tx(), Ok(()));
+            assert!(registry.is_meta_tx_paused());
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            assert_eq!(registry.register(String::from("bob")), Ok(()));
+            assert_eq!(
+                registry.get_owner(String::from("bob")),
+                Some(accounts.django)
+            );
+        }
+
+        #[ink::test]
+        fn pause_meta_tx_by_non_guardian_fails() {
             let accounts = default_accounts();
             ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
 
             let mut registry = Registry::new([0x0; 32].into());
-            assert_eq!(registry.set_trusted_forwarder([0x1; 32].into()), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
             assert_eq!(
-                registry.get_trusted_forwarder(),
-                Some(AccountId::from([0x1; 32]))
+                registry.pause_meta_tx(),
+                Err(AccessControlError::MissingRole)
             );
         }
 
         #[ink::test]
-        fn set_trusted_forwarder_by_non_admin_fails() {
+        fn unpause_meta_tx_by_guardian_fails() {
             let accounts = default_accounts();
             ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
 
             let mut registry = Registry::new([0x0; 32].into());
+            assert_eq!(registry.grant_role(GUARDIAN, accounts.bob), Ok(()));
 
             ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(registry.pause_meta_tx(), Ok(()));
             assert_eq!(
-                registry.set_trusted_forwarder([0x1; 32].into()),
+                registry.unpause_meta_tx(),
                 Err(AccessControlError::MissingRole)
             );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(registry.unpause_meta_tx(), Ok(()));
+            assert!(!registry.is_meta_tx_paused());
+        }
+
+        #[ink::test]
+        fn constructor_grants_roles_to_deployer() {
+            let accounts = default_accounts();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            let registry = Registry::new([0x0; 32].into());
+            assert!(registry.has_role(DEFAULT_ADMIN_ROLE, accounts.alice));
+            assert!(registry.has_role(MANAGER, accounts.alice));
+            assert!(registry.has_role(GUARDIAN, accounts.alice));
+        }
+
+        #[ink::test]
+        fn meta_tx_context_outside_meta_tx() {
+            let accounts = default_accounts();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+
+            let mut registry = Registry::new(accounts.django);
+            assert!(!registry._is_meta_tx());
+            assert_eq!(registry._msg_sender(), Ok(accounts.alice));
+            assert_eq!(registry._relayer(), Ok(None));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            assert!(registry._is_meta_tx());
+
+            // While paused, calls from the trusted forwarder are not meta transactions either
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(registry.pause_meta_tx(), Ok(()));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            assert!(!registry._is_meta_tx());
+            assert_eq!(registry._msg_sender(), Ok(accounts.django));
+            assert_eq!(registry._relayer(), Ok(None));
+        }
+
+        #[ink::test]
+        fn split_trailing_accounts_works() {
+            let accounts = default_accounts();
+
+            let mut input = vec![0x1, 0x2, 0x3, 0x4, 0x5];
+            input.extend_from_slice(accounts.eve.as_ref());
+            input.extend_from_slice(accounts.bob.as_ref());
+            assert_eq!(
+                split_trailing_accounts(&input),
+                Ok((&[0x1, 0x2, 0x3, 0x4, 0x5][..], accounts.eve, accounts.bob))
+            );
+            assert_eq!(
+                split_trailing_accounts(&[0x0; 63]),
+                Err(meta_tx_context::Error::RecoverAccountIdFailed)
+            );
+        }
+
+        /// Result of a call that needs a role the caller may not have.
+        fn allowed_if(has_role: bool) -> Result<(), AccessControlError> {
+            if has_role {
+                Ok(())
+            } else {
+                Err(AccessControlError::MissingRole)
+            }
+        }
+
+        #[ink::test]
+        fn roles_are_enforced_for_every_combination() {
+            let accounts = default_accounts();
+
+            for combination in 0..8u8 {
+                let is_admin = combination & 1 != 0;
+                let is_manager = combination & 2 != 0;
+                let is_guardian = combination & 4 != 0;
+
+                // Roles live in mappings, so every registry needs its own storage
+                ink::env::test::set_callee::<ink::env::DefaultEnvironment>(AccountId::from(
+                    [0x10 + combination; 32],
+                ));
+                ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+                let mut registry = Registry::new([0x0; 32].into());
+                for (role, granted) in [
+                    (DEFAULT_ADMIN_ROLE, is_admin),
+                    (MANAGER, is_manager),
+                    (GUARDIAN, is_guardian),
+                ] {
+                    if granted {
+                        assert_eq!(registry.grant_role(role, accounts.bob), Ok(()));
+                    }
+                }
+                assert_eq!(
+                    registry.propose_trusted_forwarder(TrustedForwarder::Account(accounts.eve)),
+                    Ok(())
+                );
+
+                ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+
+                // Only the admin grants and revokes roles
+                assert_eq!(
+                    registry.grant_role(GUARDIAN, accounts.charlie),
+                    allowed_if(is_admin)
+                );
+                assert_eq!(
+                    registry.revoke_role(GUARDIAN, accounts.charlie),
+                    allowed_if(is_admin)
+                );
+
+                // Only a manager rotates the trusted forwarders
+                assert_eq!(
+                    registry.propose_trusted_forwarder(TrustedForwarder::Account(accounts.frank)),
+                    allowed_if(is_manager)
+                );
+                assert_eq!(
+                    registry.cancel_trusted_forwarder_proposal(),
+                    allowed_if(is_manager).map_err(Into::into)
+                );
+                assert_eq!(
+                    registry.accept_trusted_forwarder(),
+                    allowed_if(is_manager)
+                        .map_err(Into::into)
+                        .and(Err(meta_tx_context::Error::NoPendingTrustedForwarder))
+                );
+                assert_eq!(
+                    registry.set_trusted_forwarder_delay(MIN_TRUSTED_FORWARDER_DELAY),
+                    allowed_if(is_manager).map_err(Into::into)
+                );
+                assert_eq!(
+                    registry.remove_trusted_forwarder([0x0; 32].into()),
+                    allowed_if(is_manager)
+                );
+                assert_eq!(
+                    registry.remove_trusted_code_hash([0x0; 32].into()),
+                    allowed_if(is_manager)
+                );
+                assert_eq!(registry.clear_trusted_forwarders(), allowed_if(is_manager));
+
+                // A manager or a guardian pauses, only a manager unpauses
+                assert_eq!(
+                    registry.pause_meta_tx(),
+                    allowed_if(is_manager || is_guardian)
+                );
+                assert_eq!(registry.unpause_meta_tx(), allowed_if(is_manager));
+            }
         }
     }
 }
@@ -0,0 +1,12 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink::prelude::vec::Vec;
+
+/// Implemented by contract accounts, such as multisig wallets, to approve signatures made on their behalf,
+/// like ERC-1271. Contracts have no key to sign with, so a forwarder asks them instead.
+#[ink::trait_definition]
+pub trait ContractSigner {
+    /// Returns whether `signature` is a valid signature of `hash` on behalf of this contract.
+    #[ink(message)]
+    fn is_valid_signature(&self, hash: [u8; 32], signature: Vec<u8>) -> bool;
+}
@@ -0,0 +1,33 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink::primitives::AccountId;
+
+pub type Balance = <ink::env::DefaultEnvironment as ink::env::Environment>::Balance;
+
+/// Errors a fee collector reports to its forwarder.
+#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum FeeCollectorError {
+    /// Only the forwarder may collect fees
+    CallerNotForwarder,
+    /// The token refused the transfer, e.g. because the allowance or balance is too low
+    TransferFailed,
+}
+
+/// Implemented by fee collectors to collect the PSP22 fees of a forwarder's meta transactions.
+///
+/// Signers approve the fee collector to spend their fee tokens, so `collect` must only accept calls from
+/// the forwarder, which never forwards a transaction calling it. Its selector is namespaced by the trait,
+/// so it cannot clash with messages of other contracts.
+#[ink::trait_definition]
+pub trait FeeCollector {
+    /// Transfer `amount` of `token` from `from` to `to`, spending the allowance `from` gave this contract.
+    #[ink(message)]
+    fn collect(
+        &mut self,
+        token: AccountId,
+        from: AccountId,
+        to: AccountId,
+        amount: Balance,
+    ) -> Result<(), FeeCollectorError>;
+}
@@ -3,53 +3,363 @@
 
 pub mod traits;
 
+pub use meta_tx_macro::{
+    meta_tx,
+    meta_tx_contract,
+};
 pub use traits::*;
 
-use ink::prelude::vec::Vec;
+use ink::prelude::{
+    vec,
+    vec::Vec,
+};
 use openbrush::{
     contracts::access_control::*,
     modifiers,
     traits::{
         AccountId,
+        Hash,
         Storage,
+        Timestamp,
     },
 };
 
+/// Role allowed to rotate the trusted forwarders and to pause and unpause meta transactions.
+/// `DEFAULT_ADMIN_ROLE` only grants and revokes roles.
 pub const MANAGER: RoleType = ink::selector_id!("MANAGER");
+/// Role allowed to stop honoring the trusted forwarders in an emergency, without being able to rotate them.
+pub const GUARDIAN: RoleType = ink::selector_id!("GUARDIAN");
+
+/// Shortest time in milliseconds a proposed forwarder has to wait before it can be accepted, two days.
+/// It applies to recipients that never set a delay as well.
+pub const MIN_TRUSTED_FORWARDER_DELAY: Timestamp = 2 * 24 * 60 * 60 * 1000;
+/// Longest time in milliseconds a proposed forwarder may have to wait, thirty days.
+/// Otherwise a manager could set a delay that blocks rotating the forwarders for good.
+pub const MAX_TRUSTED_FORWARDER_DELAY: Timestamp = 30 * 24 * 60 * 60 * 1000;
 
 pub const STORAGE_KEY: u32 = openbrush::storage_unique_key!(MetaTxContextData);
 
+/// The whole input of the current call, selector included.
+struct RawInput(Vec<u8>);
+
+impl scale::Decode for RawInput {
+    fn decode<I: scale::Input>(input: &mut I) -> Result<Self, scale::Error> {
+        let len = input.remaining_len()?.ok_or("unknown input length")?;
+        let mut bytes = vec![0; len];
+        input.read(&mut bytes)?;
+        Ok(Self(bytes))
+    }
+}
+
+/// Reads the input of the current call.
+fn call_input() -> Result<Vec<u8>, Error> {
+    #[cfg(feature = "std")]
+    if let Some(input) = test::call_input() {
+        return Ok(input)
+    }
+    let RawInput(input) =
+        ink::env::decode_input::<RawInput>().map_err(|_| Error::RecoverAccountIdFailed)?;
+    Ok(input)
+}
+
+/// Helpers for off-chain tests, whose environment cannot provide the input of the current call.
+#[cfg(feature = "std")]
+pub mod test {
+    use super::*;
+    use core::cell::RefCell;
+
+    std::thread_local! {
+        static CALL_INPUT: RefCell<Option<Vec<u8>>> = RefCell::new(None);
+    }
+
+    /// Set the input of the current call, selector included, read by `_caller`, `_msg_sender`,
+    /// `_relayer` and `_msg_data`.
+    pub fn set_call_input(input: Vec<u8>) {
+        CALL_INPUT.with(|call_input| *call_input.borrow_mut() = Some(input));
+    }
+
+    pub(crate) fn call_input() -> Option<Vec<u8>> {
+        CALL_INPUT.with(|call_input| call_input.borrow().clone())
+    }
+}
+
+/// Splits the input of a call from a forwarder into the call data and the relayer and signer the forwarder
+/// appended to it as 32 raw bytes each. The signer comes last, like in ERC-2771.
+pub fn split_trailing_accounts(input: &[u8]) -> Result<(&[u8], AccountId, AccountId), Error> {
+    let data_len = input
+        .len()
+        .checked_sub(64)
+        .ok_or(Error::RecoverAccountIdFailed)?;
+    let (data, accounts) = input.split_at(data_len);
+    let relayer = AccountId::try_from(&accounts[..32])?;
+    let sender = AccountId::try_from(&accounts[32..])?;
+    Ok((data, relayer, sender))
+}
+
+/// Fields are only ever appended, so contracts upgraded in place keep decoding the storage older
+/// versions wrote under `STORAGE_KEY`.
 #[derive(Default, Debug)]
 #[openbrush::upgradeable_storage(STORAGE_KEY)]
 pub struct Data {
+    /// The single forwarder trusted by earlier versions. It is still trusted along with `trusted_forwarders`,
+    /// but new forwarders are only ever added to those.
     pub trusted_forwarder: Option<AccountId>,
+    /// Forwarders whose calls carry the signer's account, so several forwarders can be trusted during a migration.
+    pub trusted_forwarders: Vec<AccountId>,
+    /// Code hashes of audited forwarder code, every contract instantiated from them is trusted.
+    pub trusted_code_hashes: Vec<Hash>,
+    /// While set, calls from the trusted forwarders are treated like any other call.
+    pub meta_tx_paused: bool,
+    /// Forwarder proposed by a manager and the time from which it can be accepted.
+    pub pending_trusted_forwarder: Option<(TrustedForwarder, Timestamp)>,
+    /// Time in milliseconds a proposed forwarder has to wait before it can be accepted, as set by a manager.
+    /// `get_trusted_forwarder_delay` bounds it by `MIN_TRUSTED_FORWARDER_DELAY` and `MAX_TRUSTED_FORWARDER_DELAY`.
+    pub trusted_forwarder_delay: Timestamp,
 }
 
 impl<T> MetaTxContext for T
 where
-    T: Storage<Data> + Storage<access_control::Data>,
+    T: Storage<Data> + Storage<access_control::Data> + AccessControl,
 {
-    default fn get_trusted_forwarder(&self) -> Option<AccountId> {
-        self.data::<Data>().trusted_forwarder
+    default fn get_trusted_forwarders(&self) -> Vec<AccountId> {
+        let data = self.data::<Data>();
+        data.trusted_forwarder
+            .into_iter()
+            .chain(data.trusted_forwarders.iter().copied())
+            .collect()
+    }
+
+    default fn is_trusted_forwarder(&self, account: AccountId) -> bool {
+        let data = self.data::<Data>();
+        if data.trusted_forwarder == Some(account) || data.trusted_forwarders.contains(&account) {
+            return true
+        }
+        let trusted_code_hashes = &self.data::<Data>().trusted_code_hashes;
+        // Only look up the code hash if there is one to match, accounts without code have none
+        !trusted_code_hashes.is_empty()
+            && matches!(
+                Self::env().code_hash(&account),
+                Ok(code_hash) if trusted_code_hashes.contains(&code_hash)
+            )
     }
 
-    #[modifiers(only_role(DEFAULT_ADMIN_ROLE))]
-    default fn set_trusted_forwarder(
+    default fn get_trusted_code_hashes(&self) -> Vec<Hash> {
+        self.data::<Data>().trusted_code_hashes.clone()
+    }
+
+    #[modifiers(only_role(MANAGER))]
+    default fn propose_trusted_forwarder(
+        &mut self,
+        forwarder: TrustedForwarder,
+    ) -> Result<(), AccessControlError> {
+        let eta = Self::env()
+            .block_timestamp()
+            .saturating_add(self.get_trusted_forwarder_delay());
+        self.data::<Data>().pending_trusted_forwarder = Some((forwarder, eta));
+        self._emit_trusted_forwarder_proposed_event(forwarder, eta);
+        Ok(())
+    }
+
+    #[modifiers(only_role(MANAGER))]
+    default fn accept_trusted_forwarder(&mut self) -> Result<(), Error> {
+        let (forwarder, eta) = self
+            .data::<Data>()
+            .pending_trusted_forwarder
+            .ok_or(Error::NoPendingTrustedForwarder)?;
+        if Self::env().block_timestamp() < eta {
+            return Err(Error::TrustedForwarderDelayNotPassed)
+        }
+        self.data::<Data>().pending_trusted_forwarder = None;
+        self._add_trusted_forwarder(forwarder);
+        Ok(())
+    }
+
+    #[modifiers(only_role(MANAGER))]
+    default fn cancel_trusted_forwarder_proposal(&mut self) -> Result<(), Error> {
+        self.data::<Data>()
+            .pending_trusted_forwarder
+            .take()
+            .ok_or(Error::NoPendingTrustedForwarder)?;
+        Ok(())
+    }
+
+    default fn get_pending_trusted_forwarder(&self) -> Option<(TrustedForwarder, Timestamp)> {
+        self.data::<Data>().pending_trusted_forwarder
+    }
+
+    #[modifiers(only_role(MANAGER))]
+    default fn remove_trusted_forwarder(
         &mut self,
         forwarder: AccountId,
     ) -> Result<(), AccessControlError> {
-        self.data::<Data>().trusted_forwarder = Some(forwarder);
+        if self.data::<Data>().trusted_forwarder == Some(forwarder) {
+            self.data::<Data>().trusted_forwarder = None;
+            self._emit_trusted_forwarder_changed_event(TrustedForwarder::Account(forwarder), false);
+        }
+        let trusted_forwarders = &mut self.data::<Data>().trusted_forwarders;
+        if let Some(index) = trusted_forwarders
+            .iter()
+            .position(|trusted| *trusted == forwarder)
+        {
+            trusted_forwarders.remove(index);
+            self._emit_trusted_forwarder_changed_event(TrustedForwarder::Account(forwarder), false);
+        }
         Ok(())
     }
 
-    default fn _caller(&self, data: Vec<u8>) -> Result<AccountId, Error> {
+    #[modifiers(only_role(MANAGER))]
+    default fn remove_trusted_code_hash(
+        &mut self,
+        code_hash: Hash,
+    ) -> Result<(), AccessControlError> {
+        let trusted_code_hashes = &mut self.data::<Data>().trusted_code_hashes;
+        if let Some(index) = trusted_code_hashes
+            .iter()
+            .position(|trusted| *trusted == code_hash)
+        {
+            trusted_code_hashes.remove(index);
+            self._emit_trusted_forwarder_changed_event(
+                TrustedForwarder::CodeHash(code_hash),
+                false,
+            );
+        }
+        Ok(())
+    }
+
+    #[modifiers(only_role(MANAGER))]
+    default fn clear_trusted_forwarders(&mut self) -> Result<(), AccessControlError> {
+        let data = self.data::<Data>();
+        let legacy_forwarder = data.trusted_forwarder.take();
+        let forwarders = core::mem::take(&mut data.trusted_forwarders);
+        let code_hashes = core::mem::take(&mut data.trusted_code_hashes);
+        for forwarder in legacy_forwarder.into_iter().chain(forwarders) {
+            self._emit_trusted_forwarder_changed_event(TrustedForwarder::Account(forwarder), false);
+        }
+        for code_hash in code_hashes {
+            self._emit_trusted_forwarder_changed_event(
+                TrustedForwarder::CodeHash(code_hash),
+                false,
+            );
+        }
+        Ok(())
+    }
+
+    default fn get_trusted_forwarder_delay(&self) -> Timestamp {
+        // Delays stored before the bounds existed may lie outside of them
+        self.data::<Data>()
+            .trusted_forwarder_delay
+            .clamp(MIN_TRUSTED_FORWARDER_DELAY, MAX_TRUSTED_FORWARDER_DELAY)
+    }
+
+    #[modifiers(only_role(MANAGER))]
+    default fn set_trusted_forwarder_delay(&mut self, delay: Timestamp) -> Result<(), Error> {
+        if delay < self.get_trusted_forwarder_delay() {
+            return Err(Error::TrustedForwarderDelayTooShort)
+        }
+        if delay > MAX_TRUSTED_FORWARDER_DELAY {
+            return Err(Error::TrustedForwarderDelayTooLong)
+        }
+        self.data::<Data>().trusted_forwarder_delay = delay;
+        Ok(())
+    }
+
+    default fn is_meta_tx_paused(&self) -> bool {
+        self.data::<Data>().meta_tx_paused
+    }
+
+    default fn pause_meta_tx(&mut self) -> Result<(), AccessControlError> {
         let caller = Self::env().caller();
-        if let Some(trusted_forwarder) = self.data::<Data>().trusted_forwarder {
-            if caller == trusted_forwarder {
-                return AccountId::try_from(data.as_slice())
-                    .map_err(|_| Error::RecoverAccountIdFailed)
+        if !self.has_role(MANAGER, caller) && !self.has_role(GUARDIAN, caller) {
+            return Err(AccessControlError::MissingRole)
+        }
+        self.data::<Data>().meta_tx_paused = true;
+        Ok(())
+    }
+
+    #[modifiers(only_role(MANAGER))]
+    default fn unpause_meta_tx(&mut self) -> Result<(), AccessControlError> {
+        self.data::<Data>().meta_tx_paused = false;
+        Ok(())
+    }
+
+    default fn _is_meta_tx(&self) -> bool {
+        !self.data::<Data>().meta_tx_paused && self.is_trusted_forwarder(Self::env().caller())
+    }
+
+    default fn _caller(&self, data: Vec<u8>) -> Result<AccountId, Error> {
+        if !self._is_meta_tx() {
+            return Ok(Self::env().caller())
+        }
+        // Only the signer appended last comes from the forwarder, the signed input can place any
+        // `data` argument before it
+        let sender = self._msg_sender()?;
+        if data.as_slice() != AsRef::<[u8]>::as_ref(&sender) {
+            return Err(Error::SenderMismatch)
+        }
+        Ok(sender)
+    }
+
+    default fn _msg_sender(&self) -> Result<AccountId, Error> {
+        if !self._is_meta_tx() {
+            return Ok(Self::env().caller())
+        }
+        let input = call_input()?;
+        let (_, _, sender) = split_trailing_accounts(&input)?;
+        Ok(sender)
+    }
+
+    default fn _relayer(&self) -> Result<Option<AccountId>, Error> {
+        if !self._is_meta_tx() {
+            return Ok(None)
+        }
+        let input = call_input()?;
+        let (_, relayer, _) = split_trailing_accounts(&input)?;
+        Ok(Some(relayer))
+    }
+
+    default fn _msg_data(&self) -> Result<Vec<u8>, Error> {
+        let input = call_input()?;
+        if !self._is_meta_tx() {
+            return Ok(input)
+        }
+        let (data, ..) = split_trailing_accounts(&input)?;
+        Ok(data.to_vec())
+    }
+
+    default fn _add_trusted_forwarder(&mut self, forwarder: TrustedForwarder) {
+        let data = self.data::<Data>();
+        let added = match forwarder {
+            TrustedForwarder::Account(account)
+                if data.trusted_forwarder != Some(account)
+                    && !data.trusted_forwarders.contains(&account) =>
+            {
+                data.trusted_forwarders.push(account);
+                true
             }
+            TrustedForwarder::CodeHash(code_hash)
+                if !data.trusted_code_hashes.contains(&code_hash) =>
+            {
+                data.trusted_code_hashes.push(code_hash);
+                true
+            }
+            _ => false,
+        };
+        if added {
+            self._emit_trusted_forwarder_changed_event(forwarder, true);
         }
-        Ok(caller)
+    }
+
+    default fn _emit_trusted_forwarder_proposed_event(
+        &self,
+        _forwarder: TrustedForwarder,
+        _eta: Timestamp,
+    ) {
+    }
+
+    default fn _emit_trusted_forwarder_changed_event(
+        &self,
+        _forwarder: TrustedForwarder,
+        _trusted: bool,
+    ) {
     }
 }
@@ -2,21 +2,121 @@ use core::array::TryFromSliceError;
 use ink::prelude::vec::Vec;
 use openbrush::{
     contracts::access_control::*,
-    traits::AccountId,
+    traits::{
+        AccountId,
+        Hash,
+        Timestamp,
+    },
 };
 
+/// A forwarder a recipient can trust: a single contract, or every contract instantiated from some code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub enum TrustedForwarder {
+    Account(AccountId),
+    CodeHash(Hash),
+}
+
 #[openbrush::wrapper]
 pub type MetaTxContextRef = dyn MetaTxContext;
 
 #[openbrush::trait_definition]
 pub trait MetaTxContext {
     #[ink(message)]
-    fn get_trusted_forwarder(&self) -> Option<AccountId>;
+    fn get_trusted_forwarders(&self) -> Vec<AccountId>;
+
+    /// Returns whether `account` is a trusted forwarder, either by address or by code hash.
+    #[ink(message)]
+    fn is_trusted_forwarder(&self, account: AccountId) -> bool;
+
+    #[ink(message)]
+    fn get_trusted_code_hashes(&self) -> Vec<Hash>;
+
+    /// Propose to trust `forwarder` in addition to the already trusted forwarders, replacing any pending proposal.
+    /// It can be accepted once the trusted forwarder delay has passed. Manager only.
+    #[ink(message)]
+    fn propose_trusted_forwarder(
+        &mut self,
+        forwarder: TrustedForwarder,
+    ) -> Result<(), AccessControlError>;
+
+    /// Trust the pending forwarder once its delay has passed. Manager only.
+    #[ink(message)]
+    fn accept_trusted_forwarder(&mut self) -> Result<(), Error>;
+
+    /// Drop the pending forwarder. Manager only.
+    #[ink(message)]
+    fn cancel_trusted_forwarder_proposal(&mut self) -> Result<(), Error>;
+
+    /// Returns the pending forwarder and the time from which it can be accepted.
+    #[ink(message)]
+    fn get_pending_trusted_forwarder(&self) -> Option<(TrustedForwarder, Timestamp)>;
+
+    /// Stop trusting `forwarder`, effective immediately. Manager only.
+    #[ink(message)]
+    fn remove_trusted_forwarder(&mut self, forwarder: AccountId) -> Result<(), AccessControlError>;
+
+    /// Stop trusting contracts instantiated from `code_hash`, effective immediately. Manager only.
+    #[ink(message)]
+    fn remove_trusted_code_hash(&mut self, code_hash: Hash) -> Result<(), AccessControlError>;
+
+    /// Stop trusting any forwarder, effective immediately. Manager only.
+    #[ink(message)]
+    fn clear_trusted_forwarders(&mut self) -> Result<(), AccessControlError>;
+
+    /// Returns the time in milliseconds a proposed forwarder has to wait before it can be accepted, at least
+    /// `MIN_TRUSTED_FORWARDER_DELAY`.
+    #[ink(message)]
+    fn get_trusted_forwarder_delay(&self) -> Timestamp;
+
+    /// Set the time in milliseconds a proposed forwarder has to wait before it can be accepted. The delay can only
+    /// grow, so a compromised manager key cannot skip it, and never beyond `MAX_TRUSTED_FORWARDER_DELAY`, so it
+    /// cannot block rotating the forwarders for good. Manager only.
+    #[ink(message)]
+    fn set_trusted_forwarder_delay(&mut self, delay: Timestamp) -> Result<(), Error>;
 
+    /// Returns whether calls from the trusted forwarders are currently treated like any other call.
     #[ink(message)]
-    fn set_trusted_forwarder(&mut self, forwarder: AccountId) -> Result<(), AccessControlError>;
+    fn is_meta_tx_paused(&self) -> bool;
+
+    /// Stop honoring the trusted forwarders, `_caller` falls back to the immediate caller. Manager or guardian only.
+    #[ink(message)]
+    fn pause_meta_tx(&mut self) -> Result<(), AccessControlError>;
+
+    /// Honor the trusted forwarders again. Manager only.
+    #[ink(message)]
+    fn unpause_meta_tx(&mut self) -> Result<(), AccessControlError>;
+
+    /// Returns whether the current call is a meta transaction, i.e. comes from a trusted forwarder while
+    /// meta transactions are not paused.
+    fn _is_meta_tx(&self) -> bool;
 
+    /// Returns the signer of a meta transaction, or the immediate caller otherwise. The signer is read like
+    /// `_msg_sender` does, and `data`, the argument `SenderMode::Argument` passes, must name the same account.
     fn _caller(&self, data: Vec<u8>) -> Result<AccountId, Error>;
+
+    /// Returns the signer of a meta transaction, read from the last 32 bytes the forwarder appended to the
+    /// call input, or the immediate caller otherwise. Works for messages without a `data` argument.
+    fn _msg_sender(&self) -> Result<AccountId, Error>;
+
+    /// Returns the account that submitted a meta transaction to the forwarder, or `None` for other calls.
+    fn _relayer(&self) -> Result<Option<AccountId>, Error>;
+
+    /// Returns the input of the current call, selector included, without the relayer and signer a forwarder
+    /// appended to it.
+    fn _msg_data(&self) -> Result<Vec<u8>, Error>;
+
+    /// Trust `forwarder` without a delay, e.g. in a constructor.
+    fn _add_trusted_forwarder(&mut self, forwarder: TrustedForwarder);
+
+    /// User must override this method in their contract.
+    fn _emit_trusted_forwarder_proposed_event(&self, _forwarder: TrustedForwarder, _eta: Timestamp);
+
+    /// User must override this method in their contract.
+    fn _emit_trusted_forwarder_changed_event(&self, _forwarder: TrustedForwarder, _trusted: bool);
 }
 
 impl From<TryFromSliceError> for Error {
@@ -30,4 +130,21 @@ impl From<TryFromSliceError> for Error {
 #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
 pub enum Error {
     RecoverAccountIdFailed,
+    /// There is no pending forwarder
+    NoPendingTrustedForwarder,
+    /// The delay of the pending forwarder has not passed yet
+    TrustedForwarderDelayNotPassed,
+    /// The trusted forwarder delay cannot be shortened
+    TrustedForwarderDelayTooShort,
+    /// The trusted forwarder delay cannot exceed `MAX_TRUSTED_FORWARDER_DELAY`
+    TrustedForwarderDelayTooLong,
+    /// The account passed to `_caller` is not the signer the forwarder appended to the call input
+    SenderMismatch,
+    AccessControlError(AccessControlError),
+}
+
+impl From<AccessControlError> for Error {
+    fn from(err: AccessControlError) -> Self {
+        Error::AccessControlError(err)
+    }
 }
@@ -0,0 +1,203 @@
+//! Attribute macros resolving the effective caller of meta transaction aware ink! messages.
+//!
+//! `#[ink::contract]` expands before the attributes of the items inside its module, so the
+//! messages are rewritten by `#[meta_tx_contract]`, which must be placed above `#[ink::contract]`:
+//!
+//! ```ignore
+//! #[meta_tx_context::meta_tx_contract]
+//! #[ink::contract]
+//! mod registry {
+//!     impl Registry {
+//!         #[ink(message)]
+//!         #[meta_tx]
+//!         pub fn register(&mut self, name: String) -> Result<(), Error> {
+//!             // `caller` is the signer when called through a trusted forwarder
+//!             self.names.insert(caller, &name);
+//!             Ok(())
+//!         }
+//!     }
+//! }
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::{
+    Span,
+    TokenStream as TokenStream2,
+};
+use quote::{
+    format_ident,
+    quote,
+};
+use syn::{
+    parse_macro_input,
+    parse_quote,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    Attribute,
+    FnArg,
+    Ident,
+    ImplItem,
+    ImplItemMethod,
+    Item,
+    ItemMod,
+    Meta,
+    NestedMeta,
+    Pat,
+    ReturnType,
+    Token,
+    Type,
+};
+
+/// Binding of the caller when `#[meta_tx]` does not name one.
+const DEFAULT_BINDING: &str = "caller";
+
+/// Rewrites every `#[meta_tx]` message of the annotated contract module.
+///
+/// Each of them starts by binding the caller resolved by `MetaTxContext::_msg_sender` to `caller`, or to
+/// the identifier given as `#[meta_tx(sender)]`. Their arguments are left untouched, the signer of a meta
+/// transaction is read from the bytes the forwarder appends to the call input.
+#[proc_macro_attribute]
+pub fn meta_tx_contract(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return syn::Error::new(
+            TokenStream2::from(attr).span(),
+            "#[meta_tx_contract] takes no arguments",
+        )
+        .to_compile_error()
+        .into()
+    }
+    let mut module = parse_macro_input!(item as ItemMod);
+    match expand_module(&mut module) {
+        Ok(()) => quote!(#module).into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Marks an ink! message whose caller is resolved through `MetaTxContext`.
+///
+/// Only valid inside a module annotated with `#[meta_tx_contract]`, which consumes it.
+#[proc_macro_attribute]
+pub fn meta_tx(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let item = TokenStream2::from(item);
+    let err = syn::Error::new(
+        Span::call_site(),
+        "#[meta_tx] must be used inside a module annotated with #[meta_tx_contract] placed above \
+         #[ink::contract]",
+    )
+    .to_compile_error();
+    quote!(#err #item).into()
+}
+
+fn expand_module(module: &mut ItemMod) -> syn::Result<()> {
+    let Some((_, items)) = module.content.as_mut() else {
+        return Err(syn::Error::new(
+            module.span(),
+            "#[meta_tx_contract] requires an inline module",
+        ))
+    };
+    let mut errors: Option<syn::Error> = None;
+    for item in items.iter_mut() {
+        let Item::Impl(item_impl) = item else {
+            continue
+        };
+        for impl_item in item_impl.items.iter_mut() {
+            let ImplItem::Method(method) = impl_item else {
+                continue
+            };
+            if let Err(err) = expand_method(method) {
+                match errors.as_mut() {
+                    Some(errors) => errors.combine(err),
+                    None => errors = Some(err),
+                }
+            }
+        }
+    }
+    match errors {
+        Some(errors) => Err(errors),
+        None => Ok(()),
+    }
+}
+
+fn expand_method(method: &mut ImplItemMethod) -> syn::Result<()> {
+    let Some(position) = method.attrs.iter().position(is_meta_tx_attribute) else {
+        return Ok(())
+    };
+    let attr = method.attrs.remove(position);
+    let binding = if attr.tokens.is_empty() {
+        format_ident!("{}", DEFAULT_BINDING)
+    } else {
+        attr.parse_args::<Ident>()?
+    };
+
+    check_is_message(method, &attr)?;
+    if !returns_result(&method.sig.output) {
+        let span = match &method.sig.output {
+            ReturnType::Type(..) => method.sig.output.span(),
+            ReturnType::Default => method.sig.ident.span(),
+        };
+        return Err(syn::Error::new(
+            span,
+            "#[meta_tx] messages must return a `Result` whose error implements \
+             `From<meta_tx_context::Error>`",
+        ))
+    }
+    for input in &method.sig.inputs {
+        let FnArg::Typed(arg) = input else {
+            continue
+        };
+        if matches!(&*arg.pat, Pat::Ident(pat) if pat.ident == binding) {
+            return Err(syn::Error::new(
+                arg.pat.span(),
+                format!("argument `{binding}` clashes with the caller bound by #[meta_tx]"),
+            ))
+        }
+    }
+
+    let stmts = &method.block.stmts;
+    method.block = parse_quote!({
+        let #binding = ::meta_tx_context::MetaTxContext::_msg_sender(self)?;
+        #(#stmts)*
+    });
+    Ok(())
+}
+
+fn is_meta_tx_attribute(attr: &Attribute) -> bool {
+    matches!(attr.path.segments.last(), Some(segment) if segment.ident == "meta_tx")
+}
+
+/// Messages need a receiver to call `_msg_sender` on, so constructors and plain functions are rejected.
+fn check_is_message(method: &ImplItemMethod, meta_tx: &Attribute) -> syn::Result<()> {
+    let mut is_message = false;
+    for attr in method.attrs.iter().filter(|attr| attr.path.is_ident("ink")) {
+        let metas = attr.parse_args_with(Punctuated::<NestedMeta, Token![,]>::parse_terminated)?;
+        for meta in metas {
+            match meta {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("message") => is_message = true,
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("constructor") => {
+                    return Err(syn::Error::new(
+                        meta_tx.span(),
+                        "#[meta_tx] cannot be applied to constructors",
+                    ))
+                }
+                _ => {}
+            }
+        }
+    }
+    if !is_message {
+        return Err(syn::Error::new(
+            meta_tx.span(),
+            "#[meta_tx] can only be applied to #[ink(message)] functions",
+        ))
+    }
+    Ok(())
+}
+
+fn returns_result(output: &ReturnType) -> bool {
+    let ReturnType::Type(_, ty) = output else {
+        return false
+    };
+    let Type::Path(path) = &**ty else {
+        return false
+    };
+    matches!(path.path.segments.last(), Some(segment) if segment.ident == "Result")
+}
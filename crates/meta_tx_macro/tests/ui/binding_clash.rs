@@ -0,0 +1,14 @@
+#[meta_tx_macro::meta_tx_contract]
+mod registry {
+    pub struct Registry {}
+
+    impl Registry {
+        #[ink(message)]
+        #[meta_tx]
+        pub fn register(&mut self, caller: [u8; 32]) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+}
+
+fn main() {}
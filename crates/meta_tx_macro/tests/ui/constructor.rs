@@ -0,0 +1,16 @@
+#[meta_tx_macro::meta_tx_contract]
+mod flipper {
+    pub struct Flipper {
+        value: bool,
+    }
+
+    impl Flipper {
+        #[ink(constructor)]
+        #[meta_tx]
+        pub fn new() -> Self {
+            Self { value: false }
+        }
+    }
+}
+
+fn main() {}
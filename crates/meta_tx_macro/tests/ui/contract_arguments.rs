@@ -0,0 +1,4 @@
+#[meta_tx_macro::meta_tx_contract(env = DefaultEnvironment)]
+mod flipper {}
+
+fn main() {}
@@ -0,0 +1,17 @@
+#[meta_tx_macro::meta_tx_contract]
+mod flipper {
+    pub struct Flipper {
+        value: bool,
+    }
+
+    impl Flipper {
+        #[ink(message)]
+        #[meta_tx("sender")]
+        pub fn flip(&mut self) -> Result<(), ()> {
+            self.value = !self.value;
+            Ok(())
+        }
+    }
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use meta_tx_macro::meta_tx;
+
+pub struct Flipper {
+    value: bool,
+}
+
+impl Flipper {
+    #[meta_tx]
+    pub fn flip(&mut self) -> Result<(), ()> {
+        self.value = !self.value;
+        Ok(())
+    }
+}
+
+fn main() {}
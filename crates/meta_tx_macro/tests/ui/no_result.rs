@@ -0,0 +1,16 @@
+#[meta_tx_macro::meta_tx_contract]
+mod flipper {
+    pub struct Flipper {
+        value: bool,
+    }
+
+    impl Flipper {
+        #[ink(message)]
+        #[meta_tx]
+        pub fn flip(&mut self) {
+            self.value = !self.value;
+        }
+    }
+}
+
+fn main() {}
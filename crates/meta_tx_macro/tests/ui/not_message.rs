@@ -0,0 +1,16 @@
+#[meta_tx_macro::meta_tx_contract]
+mod flipper {
+    pub struct Flipper {
+        value: bool,
+    }
+
+    impl Flipper {
+        #[meta_tx]
+        pub fn flip(&mut self) -> Result<(), ()> {
+            self.value = !self.value;
+            Ok(())
+        }
+    }
+}
+
+fn main() {}
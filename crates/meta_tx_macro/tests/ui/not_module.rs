@@ -0,0 +1,6 @@
+#[meta_tx_macro::meta_tx_contract]
+pub struct Flipper {
+    value: bool,
+}
+
+fn main() {}
@@ -0,0 +1,45 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink::primitives::AccountId;
+
+pub type Balance = <ink::env::DefaultEnvironment as ink::env::Environment>::Balance;
+
+/// Errors a paymaster reports to its forwarder.
+#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum PaymasterError {
+    /// Only the forwarder may request sponsorship
+    CallerNotForwarder,
+    /// The callee and selector are not sponsored
+    NotSponsored,
+    /// The gas limit of the transaction exceeds what the paymaster sponsors
+    GasLimitTooHigh,
+    /// The user already used up its quota
+    QuotaExceeded,
+    /// The budget cannot cover the reimbursement
+    InsufficientBudget,
+    /// Transferring balance out of the paymaster failed
+    TransferFailed,
+}
+
+/// Implemented by paymasters to sponsor the meta transactions of a forwarder.
+///
+/// Both messages must only accept calls from the forwarder, which never forwards a transaction calling them.
+/// Their selectors are namespaced by the trait, so they cannot clash with messages of other contracts.
+#[ink::trait_definition]
+pub trait Paymaster {
+    /// Check that a transaction of `from` calling `selector` of `callee` with `gas_limit` is sponsored
+    /// and count it against the quota of `from`.
+    #[ink(message)]
+    fn validate(
+        &mut self,
+        from: AccountId,
+        callee: AccountId,
+        selector: [u8; 4],
+        gas_limit: u64,
+    ) -> Result<(), PaymasterError>;
+
+    /// Pay `relayer` for the `gas_used` by a sponsored transaction. Returns the amount paid.
+    #[ink(message)]
+    fn reimburse(&mut self, relayer: AccountId, gas_used: u64) -> Result<Balance, PaymasterError>;
+}
@@ -6,8 +6,7 @@ import Flipper from "./typedContracts/constructors/flipper";
 import FlipperContract from "./typedContracts/contracts/flipper";
 import { ApiPromise, WsProvider, Keyring } from "@polkadot/api";
 import { KeyringPair } from "@polkadot/keyring/types";
-import { Transaction } from "./typedContracts/types-arguments/forwarder";
-import * as $ from "scale-codec"
+import { EVM_CHAIN_ID, signEcdsa, transaction, withSafetyMargin } from "./transaction";
 
 use(chaiAsPromised);
 
@@ -24,18 +23,6 @@ describe("Flipper meta tx test", () => {
   let alice: KeyringPair;
   let bob: KeyringPair;
 
-  const $transaction_codec = $.object(
-    $.field("from", $.sizedUint8Array(32)),
-    $.field("callee", $.sizedUint8Array(32)),
-    $.field("selector", $.sizedUint8Array(4)),
-    $.field("input", $.uint8Array),
-    $.field("transferredValue", $.u128),
-    $.field("gasLimit", $.u64),
-    $.field("allowReentry", $.bool),
-    $.field("nonce", $.u128),
-    $.field("expirationTimeSeconds", $.u64)
-  );
-
   before(async function setup(): Promise<void> {
     api = await ApiPromise.create({ provider: wsProvider });
     deployer = keyring.addFromUri("//Alice");
@@ -43,7 +30,9 @@ describe("Flipper meta tx test", () => {
     bob = keyring.addFromUri("//Bob");
 
     let forwarderConstructor = new Forwarder(api, deployer);
-    let forwarderAddress = (await forwarderConstructor.default()).address;
+    let forwarderAddress = (
+      await forwarderConstructor.new(api.genesisHash.toHex(), EVM_CHAIN_ID)
+    ).address;
     forwarder = new ForwarderContract(
         forwarderAddress,
         deployer,
@@ -91,58 +80,30 @@ describe("Flipper meta tx test", () => {
     let { value } = await flipper.query.get();
 
     // Senario 
-    // ecdsa_alice account who doesn't have any balance wants to register her name to register contract
+    // ecdsa_alice account who doesn't have any balance wants to flip the flipper contract
     // 1. ecdsa_alice sign transaction signature.
     // 2. bob (with enough balance) execute forwarder contract `execute` function with ecdsa_alice's signature.
     // 3. check the value flipped.
 
     let from: Uint8Array = ecdsa_alice.addressRaw;
     let callee: Uint8Array = keyring.decodeAddress(flipper.address);
-    let selector: number[] = [99, 58, 165, 81]; // flip [99, 58, 165, 81], flip_meta_context: [238, 188, 90, 93]
-    let input: number[] = [];
-    let transferredValue: number = 0;
-    let gasLimit: number = 1000000000;
-    let allowReentry: boolean = false;
-    let nonce: number = 0;
-    let expirationTimeSeconds: number = Date.now() + 100000000;
-
-    // Transaction to call the flip() fn in the Flipper contract
-    let transaction: Transaction = {
-        from: Array.from(from),
-        callee: Array.from(callee),
-        selector: selector,
-        input: input,
-        transferredValue: transferredValue,
-        gasLimit: gasLimit,
-        allowReentry: allowReentry,
-        nonce: nonce,
-        expirationTimeSeconds: expirationTimeSeconds
-    }
-
-    let transaction_for_encoding = {
-        from: from,
-        callee: callee,
-        selector: Uint8Array.from(selector),
-        input: Uint8Array.from(input),
-        transferredValue: BigInt(transferredValue),
-        gasLimit: BigInt(gasLimit),
-        allowReentry: transaction.allowReentry,
-        nonce: BigInt(nonce),
-        expirationTimeSeconds: BigInt(expirationTimeSeconds)
-    }
-
-    let encoded_transaction = $transaction_codec.encode(transaction_for_encoding);
-    let signature = ecdsa_alice.sign(encoded_transaction);
+    let selector: number[] = [238, 188, 90, 93]; // flip_meta_context, which returns a `Result`
+
+    // Transaction to call the flip_meta_context() fn in the Flipper contract
+    let tx = await transaction(forwarder, from, callee, selector, [], true);
+    let signature = await signEcdsa(forwarder, ecdsa_alice, tx);
 
     // Exec
-    const { gasRequired } = await forwarder
+    const { value: outcome, gasRequired } = await forwarder
       .withSigner(bob)
-      .query.execute(transaction, Array.from(signature));
+      .query.execute(tx, signature);
+    // A failing call is still executed and uses up the nonce, so only send the transaction if the call succeeds
+    expect(outcome.ok?.ok?.succeeded).to.not.be.undefined;
     
     await expect(
       forwarder.withSigner(bob).tx.execute(
-          transaction, Array.from(signature), {
-          gasLimit: gasRequired,
+          tx, signature, {
+          gasLimit: withSafetyMargin(api, gasRequired),
           value: 0,
       })
     ).to.eventually.be.fulfilled;
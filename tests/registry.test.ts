@@ -6,7 +6,7 @@ import Registry from "./typedContracts/constructors/registry";
 import RegistryContract from "./typedContracts/contracts/registry";
 import { ApiPromise, WsProvider, Keyring } from "@polkadot/api";
 import { KeyringPair } from "@polkadot/keyring/types";
-import { Transaction } from "./typedContracts/types-arguments/forwarder";
+import { EVM_CHAIN_ID, signEcdsa, transaction, withSafetyMargin } from "./transaction";
 import * as $ from "scale-codec"
 
 use(chaiAsPromised);
@@ -24,18 +24,6 @@ describe("Registry meta tx test", () => {
   let alice: KeyringPair;
   let bob: KeyringPair;
 
-  const $transaction_codec = $.object(
-    $.field("from", $.sizedUint8Array(32)),
-    $.field("callee", $.sizedUint8Array(32)),
-    $.field("selector", $.sizedUint8Array(4)),
-    $.field("input", $.uint8Array),
-    $.field("transferredValue", $.u128),
-    $.field("gasLimit", $.u64),
-    $.field("allowReentry", $.bool),
-    $.field("nonce", $.u128),
-    $.field("expirationTimeSeconds", $.u64)
-  );
-
   before(async function setup(): Promise<void> {
     api = await ApiPromise.create({ provider: wsProvider });
     deployer = keyring.addFromUri("//Alice");
@@ -43,7 +31,9 @@ describe("Registry meta tx test", () => {
     bob = keyring.addFromUri("//Bob");
 
     let forwarderConstructor = new Forwarder(api, deployer);
-    let forwarderAddress = (await forwarderConstructor.default()).address;
+    let forwarderAddress = (
+      await forwarderConstructor.new(api.genesisHash.toHex(), EVM_CHAIN_ID)
+    ).address;
     forwarder = new ForwarderContract(
         forwarderAddress,
         deployer,
@@ -66,11 +56,11 @@ describe("Registry meta tx test", () => {
     const name = "test name";
     const { gasRequired } = await registry
       .withSigner(alice)
-      .query.register(name, []);
+      .query.register(name);
 
     await expect(
       registry.withSigner(alice).tx.register(
-        name, [], {
+        name, {
         gasLimit: gasRequired,
       })
     ).to.eventually.be.fulfilled;
@@ -87,46 +77,19 @@ describe("Registry meta tx test", () => {
     let from: Uint8Array = alice.addressRaw;
     let callee: Uint8Array = keyring.decodeAddress(registry.address);
     let selector: number[] = [34, 155, 85, 63]; // register
-    let input: number[] = [];
-    let transferredValue: number = 0;
-    let gasLimit: number = 1000000000;
-    let allowReentry: boolean = false;
-    let nonce: number = 0;
-    let expirationTimeSeconds: number = Date.now() + 100000000;
-
-    let encoded_name = $.str.encode("test");
-    input = input.concat(Array.from(encoded_name));
-
-    // Transaction to call the flip() fn in the Flipper contract
-    let transaction: Transaction = {
-        from: Array.from(from),
-        callee: Array.from(callee),
-        selector: selector,
-        input: input,
-        transferredValue: transferredValue,
-        gasLimit: gasLimit,
-        allowReentry: allowReentry,
-        nonce: nonce,
-        expirationTimeSeconds: expirationTimeSeconds
-    }
-
-    let transaction_for_encoding = {
-        from: from,
-        callee: callee,
-        selector: Uint8Array.from(selector),
-        input: Uint8Array.from(input),
-        transferredValue: BigInt(transferredValue),
-        gasLimit: BigInt(gasLimit),
-        allowReentry: transaction.allowReentry,
-        nonce: BigInt(nonce),
-        expirationTimeSeconds: BigInt(expirationTimeSeconds)
-    }
-
-    let encoded_transaction = $transaction_codec.encode(transaction_for_encoding);
-    let signature = alice.sign(encoded_transaction);
-
-    let res = await forwarder.query.verfiy(transaction, Array.from(signature));
+    let input: number[] = Array.from($.str.encode("test"));
+
+    // Transaction to call the register() fn in the Registry contract
+    let tx = await transaction(forwarder, from, callee, selector, input, true);
+    let signature = await signEcdsa(forwarder, alice, tx);
+
+    let res = await forwarder.query.verfiy(tx, signature);
     expect(res.value.ok?.err).to.be.equal(undefined);
+
+    // The signature does not cover another transaction
+    tx.returnsResult = false;
+    res = await forwarder.query.verfiy(tx, signature);
+    expect(res.value.ok?.err).to.not.be.equal(undefined);
   })
 
   // With meta transaction
@@ -143,54 +106,25 @@ describe("Registry meta tx test", () => {
     let from: Uint8Array = ecdsa_alice.addressRaw;
     let callee: Uint8Array = keyring.decodeAddress(registry.address);
     let selector: number[] = [34, 155, 85, 63]; // register
-    let input: number[] = [];
-    let transferredValue: number = 0;
-    let gasLimit: number = 1000000000;
-    let allowReentry: boolean = false;
-    let nonce: number = 0;
-    let expirationTimeSeconds: number = Date.now() + 100000000;
 
     const name = "test";
-    let encoded_name = $.str.encode(name);
-    input = input.concat(Array.from(encoded_name));
-
-    // Transaction to call the flip() fn in the Flipper contract
-    let transaction: Transaction = {
-        from: Array.from(from),
-        callee: Array.from(callee),
-        selector: selector,
-        input: input,
-        transferredValue: transferredValue,
-        gasLimit: gasLimit,
-        allowReentry: allowReentry,
-        nonce: nonce,
-        expirationTimeSeconds: expirationTimeSeconds
-    }
-
-    let transaction_for_encoding = {
-        from: from,
-        callee: callee,
-        selector: Uint8Array.from(selector),
-        input: Uint8Array.from(input),
-        transferredValue: BigInt(transferredValue),
-        gasLimit: BigInt(gasLimit),
-        allowReentry: transaction.allowReentry,
-        nonce: BigInt(nonce),
-        expirationTimeSeconds: BigInt(expirationTimeSeconds)
-    }
-
-    let encoded_transaction = $transaction_codec.encode(transaction_for_encoding);
-    let signature = ecdsa_alice.sign(encoded_transaction);
+    let input: number[] = Array.from($.str.encode(name));
+
+    // Transaction to call the register() fn in the Registry contract
+    let tx = await transaction(forwarder, from, callee, selector, input, true);
+    let signature = await signEcdsa(forwarder, ecdsa_alice, tx);
 
     // Exec
-    const { gasRequired } = await forwarder
+    const { value: outcome, gasRequired } = await forwarder
       .withSigner(bob)
-      .query.execute(transaction, Array.from(signature));
+      .query.execute(tx, signature);
+    // A failing call is still executed and uses up the nonce, so only send the transaction if the call succeeds
+    expect(outcome.ok?.ok?.succeeded).to.not.be.undefined;
     
     await expect(
       forwarder.withSigner(bob).tx.execute(
-          transaction, Array.from(signature), {
-          gasLimit: gasRequired,
+          tx, signature, {
+          gasLimit: withSafetyMargin(api, gasRequired),
           value: 0,
       })
     ).to.eventually.be.fulfilled;
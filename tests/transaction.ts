@@ -0,0 +1,144 @@
+import { ApiPromise } from "@polkadot/api";
+import { KeyringPair } from "@polkadot/keyring/types";
+import { WeightV2 } from "@polkadot/types/interfaces";
+import ForwarderContract from "./typedContracts/contracts/forwarder";
+import {
+  AccountId,
+  NonceMode,
+  SenderMode,
+  Signature,
+  SignatureBuilder,
+  Transaction,
+  ValidityUnit,
+} from "./typedContracts/types-arguments/forwarder";
+import * as $ from "scale-codec"
+
+// EVM chain id the forwarder is deployed with, only part of the EIP-712 domain
+export const EVM_CHAIN_ID = 0;
+
+// Gas the forwarder keeps on top of a transaction's `gasLimit`, see `GAS_SAFETY_MARGIN`
+const GAS_SAFETY_MARGIN = 1_000_000_000;
+
+// SCALE layout of `Transaction`, in field order
+const $accountId = $.sizedUint8Array(32);
+const $transaction_codec = $.object(
+  $.field("from", $accountId),
+  $.field("callee", $accountId),
+  $.field("selector", $.sizedUint8Array(4)),
+  $.field("input", $.uint8Array),
+  $.field("senderMode", $.literalUnion([SenderMode.argument, SenderMode.trailing])),
+  $.field("returnsResult", $.bool),
+  $.field("transferredValue", $.u128),
+  $.field("tip", $.u128),
+  $.field("gasLimit", $.u64),
+  $.field("allowReentry", $.bool),
+  $.field("nonceMode", $.literalUnion([NonceMode.sequential, NonceMode.unordered])),
+  $.field("nonceKey", $.u64),
+  $.field("nonce", $.u128),
+  $.field("epoch", $.u32),
+  $.field("validityUnit", $.literalUnion([ValidityUnit.timestamp, ValidityUnit.blockNumber])),
+  $.field("validAfter", $.u64),
+  $.field("validUntil", $.u64),
+  $.field(
+    "fee",
+    $.option(
+      $.object(
+        $.field("token", $accountId),
+        $.field("amount", $.u128),
+        $.field("recipient", $.option($accountId))
+      )
+    )
+  ),
+  $.field("paymaster", $.option($accountId)),
+  $.field("relayer", $.option($accountId))
+);
+
+const toBytes = (bytes: AccountId | Array<any>) => Uint8Array.from(bytes as number[]);
+const toBigInt = (value: any) => BigInt(value.toString());
+
+// A transaction of `from` calling `selector` of `callee` with `input`, valid for an hour under
+// the current sequential nonce and epoch of `from`. It pays no value, tip or fee.
+export async function transaction(
+  forwarder: ForwarderContract,
+  from: Uint8Array,
+  callee: Uint8Array,
+  selector: number[],
+  input: number[],
+  returnsResult: boolean
+): Promise<Transaction> {
+  const nonce = (await forwarder.query.getNonce(Array.from(from))).value.ok!;
+  const epoch = (await forwarder.query.getEpoch(Array.from(from))).value.ok!;
+
+  return {
+    from: Array.from(from),
+    callee: Array.from(callee),
+    selector: selector,
+    input: input,
+    senderMode: SenderMode.trailing,
+    returnsResult: returnsResult,
+    transferredValue: 0,
+    tip: 0,
+    gasLimit: 1000000000,
+    allowReentry: false,
+    nonceMode: NonceMode.sequential,
+    nonceKey: 0,
+    nonce: nonce.toString(),
+    epoch: epoch,
+    validityUnit: ValidityUnit.timestamp,
+    validAfter: 0,
+    validUntil: Date.now() + 60 * 60 * 1000,
+    fee: null,
+    paymaster: null,
+    relayer: null,
+  };
+}
+
+// Sign `domain_separator ++ SCALE(transaction)` with the ECDSA key `signer`. polkadot.js hashes
+// the payload with blake2x256 before signing, as the forwarder expects.
+export async function signEcdsa(
+  forwarder: ForwarderContract,
+  signer: KeyringPair,
+  transaction: Transaction
+): Promise<Signature> {
+  const domainSeparator = (await forwarder.query.domainSeparator()).value.ok!;
+  const fee = transaction.fee;
+  const encoded_transaction = $transaction_codec.encode({
+    from: toBytes(transaction.from),
+    callee: toBytes(transaction.callee),
+    selector: toBytes(transaction.selector),
+    input: toBytes(transaction.input),
+    senderMode: transaction.senderMode,
+    returnsResult: transaction.returnsResult,
+    transferredValue: toBigInt(transaction.transferredValue),
+    tip: toBigInt(transaction.tip),
+    gasLimit: toBigInt(transaction.gasLimit),
+    allowReentry: transaction.allowReentry,
+    nonceMode: transaction.nonceMode,
+    nonceKey: toBigInt(transaction.nonceKey),
+    nonce: toBigInt(transaction.nonce),
+    epoch: Number(transaction.epoch.toString()),
+    validityUnit: transaction.validityUnit,
+    validAfter: toBigInt(transaction.validAfter),
+    validUntil: toBigInt(transaction.validUntil),
+    fee: fee
+      ? {
+          token: toBytes(fee.token),
+          amount: toBigInt(fee.amount),
+          recipient: fee.recipient ? toBytes(fee.recipient) : undefined,
+        }
+      : undefined,
+    paymaster: transaction.paymaster ? toBytes(transaction.paymaster) : undefined,
+    relayer: transaction.relayer ? toBytes(transaction.relayer) : undefined,
+  });
+
+  const payload = new Uint8Array([...domainSeparator, ...encoded_transaction]);
+  return SignatureBuilder.Ecdsa(Array.from(signer.sign(payload)));
+}
+
+// `gasRequired` of a dry run of `execute`, plus the margin the forwarder checks is left for the call
+export function withSafetyMargin(api: ApiPromise, gasRequired: WeightV2): WeightV2 {
+  return api.registry.createType("WeightV2", {
+    refTime: gasRequired.refTime.toBn().addn(GAS_SAFETY_MARGIN),
+    proofSize: gasRequired.proofSize,
+  }) as WeightV2;
+}